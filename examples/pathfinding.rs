@@ -10,7 +10,7 @@ fn main() {
     let collection_name = "microban_1";
 
     let collection = Collection::parse(&collection_name).expect("Failed to load level set");
-    let mut game = Game::new(collection);
+    let mut game = Game::new(collection).expect("Failed to start game");
 
     let (sender, receiver) = channel();
     game.listen_to(receiver);
@@ -0,0 +1,44 @@
+use sokoban_backend as backend;
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::backend::Collection;
+
+/// Convert a single `.slc` file, writing the result to `output_dir`.
+fn convert(path: &Path, output_dir: &Path) {
+    let collection = Collection::parse_path(path).expect("Failed to load level set");
+
+    let mut output = output_dir.join(path.file_name().unwrap());
+    output.set_extension("lvl");
+    fs::write(&output, collection.to_lvl_string()).expect("Failed to write level set");
+    println!("{} -> {}", path.display(), output.display());
+}
+
+/// Usage: slc2lvl INPUT [OUTPUT_DIR]
+///
+/// INPUT may be a single `.slc` file or a directory containing several of them. The converted
+/// collections are written to OUTPUT_DIR, which defaults to the directory the input is in.
+fn main() {
+    colog::init();
+
+    let mut args = env::args().skip(1);
+    let input = PathBuf::from(args.next().expect("No input file or directory given"));
+
+    if input.is_dir() {
+        let output_dir = args.next().map_or_else(|| input.clone(), PathBuf::from);
+        for entry in fs::read_dir(&input).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().and_then(|x| x.to_str()) == Some("slc") {
+                convert(&path, &output_dir);
+            }
+        }
+    } else {
+        let output_dir = args.next().map_or_else(
+            || input.parent().unwrap_or_else(|| Path::new(".")).to_owned(),
+            PathBuf::from,
+        );
+        convert(&input, &output_dir);
+    }
+}
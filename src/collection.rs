@@ -1,7 +1,8 @@
 //! This module contains code to parse all supported collection formats.
 
 use std::fs::File;
-use std::io::Read;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
 
 use crate::level::*;
 use crate::util::*;
@@ -11,51 +12,194 @@ enum FileFormat {
     Xml,
 }
 
+/// Information about a collection and its author.
+#[derive(Debug, Clone, Default)]
+pub struct CollectionMetadata {
+    /// The full name of the collection.
+    pub title: String,
+
+    pub description: Option<String>,
+
+    pub author_email: Option<String>,
+
+    pub url: Option<String>,
+}
+
+/// Is the given block of text a level's title rather than the level itself? Titles are short,
+/// consist of a single line and contain none of the characters used for walls, crates and the
+/// worker.
+fn is_level_title(block: &str) -> bool {
+    const MAX_TITLE_LENGTH: usize = 40;
+
+    let block = block.trim();
+    !block.contains('\n')
+        && block.len() <= MAX_TITLE_LENGTH
+        && !block.contains(&['#', '$', '*', '@', '+'][..])
+}
+
+/// Read the whole file into a string.
+fn read_to_string(mut file: File) -> Result<String, SokobanError> {
+    let mut content = String::new();
+    file.read_to_string(&mut content)?;
+    Ok(content)
+}
+
+/// Turn empty strings into `None`.
+fn non_empty(s: String) -> Option<String> {
+    if s.is_empty() {
+        None
+    } else {
+        Some(s)
+    }
+}
+
+/// The text of a single level as found in a collection file, kept so the level can be parsed
+/// later on.
+#[derive(Debug, Clone)]
+struct RawLevel {
+    title: Option<String>,
+    content: String,
+}
+
+impl RawLevel {
+    fn parse(&self, rank: usize, options: ParseOptions) -> Result<Level, SokobanError> {
+        let mut level = Level::parse_with_options(rank, &self.content, options)?;
+        level.title = self.title.clone();
+        Ok(level)
+    }
+}
+
 /// A collection of levels. This type contains logic for parsing a collection file. Other than
 /// that, it is simply a list of Levels together with some metadata.
 #[derive(Debug)]
 pub struct Collection {
-    /// The full name of the collection.
-    name: String,
+    /// Title, description and author of the collection.
+    metadata: CollectionMetadata,
 
     /// The name of the file containing the level collection.
     short_name: String,
 
-    description: Option<String>,
-
     number_of_levels: usize,
 
     /// All levels of this collection. This variable is only written to when loading the
     /// collection.
     levels: Vec<Level>,
+
+    /// The unparsed text of every level, even if only the metadata has been loaded.
+    raw_levels: Vec<RawLevel>,
+
+    /// The checks performed when parsing the levels.
+    options: ParseOptions,
 }
 
 impl Collection {
     #[cfg(test)]
     pub fn from_levels(name: &str, levels: &[Level]) -> Collection {
         Collection {
-            name: name.into(),
+            metadata: CollectionMetadata {
+                title: name.into(),
+                ..CollectionMetadata::default()
+            },
             short_name: name.into(),
-            description: None,
             number_of_levels: levels.len(),
             levels: levels.into(),
+            raw_levels: levels
+                .iter()
+                .map(|level| RawLevel {
+                    title: level.title.clone(),
+                    content: level.to_string(),
+                })
+                .collect(),
+            options: ParseOptions::default(),
+        }
+    }
+
+    /// Combine several collections into one containing all their levels in order. The new
+    /// collection uses `name` both as its title and its short name. All collections must have
+    /// been loaded with the same options. The levels are only parsed if they had been parsed in
+    /// every one of the collections, i.e. none of them came from `parse_metadata`.
+    pub fn merge(collections: Vec<Collection>, name: &str) -> Result<Collection, SokobanError> {
+        let options = collections
+            .first()
+            .map_or_else(ParseOptions::default, |collection| collection.options);
+        let mut parse_levels = true;
+        let mut raw_levels = vec![];
+        for collection in collections {
+            if collection.options != options {
+                return Err(SokobanError::ParseOptionsMismatch(collection.short_name));
+            }
+            parse_levels &= collection.levels.len() == collection.raw_levels.len();
+            raw_levels.extend(collection.raw_levels);
         }
+
+        // Parse the levels again so their ranks match their position in the merged collection.
+        let levels = if parse_levels {
+            Collection::parse_raw_levels(&raw_levels, options)?
+        } else {
+            vec![]
+        };
+
+        Ok(Collection {
+            metadata: CollectionMetadata {
+                title: name.into(),
+                ..CollectionMetadata::default()
+            },
+            short_name: name.into(),
+            number_of_levels: raw_levels.len(),
+            levels,
+            raw_levels,
+            options,
+        })
     }
 
     /// Load a level set with the given name, whatever the format might be.
     pub fn parse(short_name: &str) -> Result<Collection, SokobanError> {
-        Collection::parse_helper(short_name, true)
+        Collection::parse_helper(short_name, true, ParseOptions::default())
+    }
+
+    /// Load a level set with the given name, performing the checks selected in `options` on
+    /// each level.
+    pub fn parse_with_options(
+        short_name: &str,
+        options: ParseOptions,
+    ) -> Result<Collection, SokobanError> {
+        Collection::parse_helper(short_name, true, options)
     }
 
     /// Figure out title, description, number of levels, etc. of a collection without parsing each
     /// level.
     pub fn parse_metadata(short_name: &str) -> Result<Collection, SokobanError> {
-        Collection::parse_helper(short_name, false)
+        Collection::parse_helper(short_name, false, ParseOptions::default())
     }
 
-    fn parse_helper(short_name: &str, parse_levels: bool) -> Result<Collection, SokobanError> {
-        let mut level_path = ASSETS.clone();
-        level_path.push("levels");
+    /// Load the level set stored in the given file. The format is determined by the file’s
+    /// extension, `.slc` meaning XML and anything else the usual ASCII format.
+    pub fn parse_path(path: &Path) -> Result<Collection, SokobanError> {
+        let options = ParseOptions::default();
+        let short_name = path
+            .file_stem()
+            .and_then(|x| x.to_str())
+            .unwrap_or_default();
+        let file = File::open(path)?;
+
+        if path.extension().and_then(|x| x.to_str()) == Some("slc") {
+            Collection::parse_xml(short_name, BufReader::new(file), true, options)
+        } else {
+            Collection::parse_lvl(short_name, &read_to_string(file)?, true, options)
+        }
+    }
+
+    /// Load a collection in the ASCII format from a string rather than a file.
+    pub fn from_lvl_str(short_name: &str, content: &str) -> Result<Collection, SokobanError> {
+        Collection::parse_lvl(short_name, content, true, ParseOptions::default())
+    }
+
+    fn parse_helper(
+        short_name: &str,
+        parse_levels: bool,
+        options: ParseOptions,
+    ) -> Result<Collection, SokobanError> {
+        let mut level_path = levels_dir()?;
         level_path.push(short_name);
 
         let (level_file, file_format) = {
@@ -72,69 +216,146 @@ impl Collection {
         };
 
         Ok(match file_format {
-            FileFormat::Ascii => Collection::parse_lvl(short_name, level_file, parse_levels)?,
-            FileFormat::Xml => Collection::parse_xml(short_name, level_file, parse_levels)?,
+            FileFormat::Ascii => Collection::parse_lvl(
+                short_name,
+                &read_to_string(level_file)?,
+                parse_levels,
+                options,
+            )?,
+            FileFormat::Xml => Collection::parse_xml(
+                short_name,
+                BufReader::new(level_file),
+                parse_levels,
+                options,
+            )?,
         })
     }
 
-    /// Load a file containing a bunch of levels separated by an empty line, i.e. the usual ASCII
-    /// format.
+    /// Parse the contents of a file containing a bunch of levels separated by an empty line, i.e.
+    /// the usual ASCII format.
     fn parse_lvl(
         short_name: &str,
-        file: File,
+        content: &str,
         parse_levels: bool,
+        options: ParseOptions,
     ) -> Result<Collection, SokobanError> {
-        #[cfg(unix)]
-        const EMPTY_LINE: &str = "\n\n";
-        #[cfg(windows)]
-        const EMPTY_LINE: &str = "\r\n\r\n";
         let eol = |c| c == '\n' || c == '\r';
-        let mut file = file;
-
-        // Read the collection’s file
-        let mut content = "".to_string();
-        file.read_to_string(&mut content)?;
 
+        // Files may use any line endings, no matter the platform.
+        let content = content.replace('\r', "");
         let level_strings: Vec<_> = content
-            .split(EMPTY_LINE)
+            .split("\n\n")
             .map(|x| x.trim_matches(&eol))
             .filter(|x| !x.is_empty())
             .collect();
         let name = level_strings[0].lines().next().unwrap();
         let description = level_strings[0]
-            .splitn(1, &eol)
-            .last()
-            .map(|x| x.trim().to_owned());
-
-        // Parse the individual levels
-        let (num, levels) = {
-            if parse_levels {
-                let lvls = level_strings[1..]
-                    .iter()
-                    .enumerate()
-                    .map(|(i, l)| Level::parse(i, l.trim_matches(&eol)))
-                    .collect::<Result<Vec<_>, _>>()?;
-                (lvls.len(), lvls)
+            .split_once('\n')
+            .map(|(_, x)| x.trim().to_owned())
+            .and_then(non_empty);
+
+        // Some collections put the title of each level in a block of its own before the level.
+        let mut title = None;
+        let mut raw_levels = vec![];
+        for block in &level_strings[1..] {
+            if is_level_title(block) {
+                title = Some(block.trim().to_string());
             } else {
-                (level_strings.len() - 1, vec![])
+                raw_levels.push(RawLevel {
+                    title: title.take(),
+                    content: block.to_string(),
+                });
             }
+        }
+
+        // Parse the individual levels
+        let levels = if parse_levels {
+            Collection::parse_raw_levels(&raw_levels, options)?
+        } else {
+            vec![]
         };
 
         Ok(Collection {
-            name: name.to_string(),
+            metadata: CollectionMetadata {
+                title: name.to_string(),
+                description,
+                author_email: None,
+                url: None,
+            },
             short_name: short_name.to_string(),
-            description,
-            number_of_levels: num,
+            number_of_levels: raw_levels.len(),
             levels,
+            raw_levels,
+            options,
         })
     }
 
-    /// Load a level set in the XML-based .slc format.
-    fn parse_xml(
+    fn parse_raw_levels(
+        raw_levels: &[RawLevel],
+        options: ParseOptions,
+    ) -> Result<Vec<Level>, SokobanError> {
+        raw_levels
+            .iter()
+            .enumerate()
+            .map(|(i, raw)| raw.parse(i, options))
+            .collect()
+    }
+
+    /// Load a level set in the XML-based .slc format. If no title is given, the short name is
+    /// used instead.
+    fn parse_xml<R: BufRead>(
         short_name: &str,
-        file: File,
+        file: R,
         parse_levels: bool,
+        options: ParseOptions,
     ) -> Result<Collection, SokobanError> {
+        let mut raw_levels = vec![];
+        let mut metadata = Collection::read_xml(file, |raw| {
+            raw_levels.push(raw);
+            Ok(())
+        })?;
+
+        if metadata.title.is_empty() {
+            metadata.title = short_name.to_string();
+        }
+
+        let levels = if parse_levels {
+            Collection::parse_raw_levels(&raw_levels, options)?
+        } else {
+            vec![]
+        };
+
+        Ok(Collection {
+            metadata,
+            short_name: short_name.to_string(),
+            number_of_levels: raw_levels.len(),
+            levels,
+            raw_levels,
+            options,
+        })
+    }
+
+    /// Parse a level set in the .slc format, passing each level to `f` as soon as it has been
+    /// read instead of keeping all of them in memory. Return the collection's metadata, which
+    /// has an empty title if the file does not specify one.
+    pub fn parse_xml_each<R: BufRead>(
+        file: R,
+        options: ParseOptions,
+        mut f: impl FnMut(Level),
+    ) -> Result<CollectionMetadata, SokobanError> {
+        let mut rank = 0;
+        Collection::read_xml(file, |raw| {
+            f(raw.parse(rank, options)?);
+            rank += 1;
+            Ok(())
+        })
+    }
+
+    /// Read a level set in the .slc format, calling `on_level` for each level as it is found.
+    fn read_xml<R: BufRead>(
+        file: R,
+        mut on_level: impl FnMut(RawLevel) -> Result<(), SokobanError>,
+    ) -> Result<CollectionMetadata, SokobanError> {
         use quick_xml::events::Event;
         use quick_xml::Reader;
 
@@ -147,92 +368,99 @@ impl Collection {
             Line,
         }
 
-        let file = ::std::io::BufReader::new(file);
         let mut reader = Reader::from_reader(file);
 
         let mut state = State::Nothing;
+        // How many elements have been opened but not closed yet
+        let mut depth = 0;
 
         // Collection attributes
         let mut title = String::new();
         let mut description = String::new();
         let mut email = String::new();
         let mut url = String::new();
-        let mut levels = vec![];
 
         // Level attributes
-        let mut num = 0;
         let mut level_lines = String::new();
 
         let mut buf = Vec::new();
         loop {
             match reader.read_event(&mut buf) {
-                Ok(Event::Start(ref e)) => match e.name() {
-                    b"Title" => {
-                        state = State::Title;
-                        title.clear();
-                    }
-                    b"Description" => state = State::Description,
-                    b"Email" => state = State::Email,
-                    b"Url" => state = State::Url,
-                    b"Level" => level_lines.clear(),
-                    b"L" => state = State::Line,
-                    _ => {}
-                },
-
-                Ok(Event::End(e)) => match e.name() {
-                    b"Title" | b"Description" | b"Email" | b"Url" => state = State::Nothing,
-                    b"Level" => {
-                        if parse_levels {
-                            levels.push(Level::parse(num, &level_lines)?);
+                Ok(Event::Start(ref e)) => {
+                    depth += 1;
+                    match e.name() {
+                        b"Title" => {
+                            state = State::Title;
+                            title.clear();
                         }
-                        num += 1;
-                    }
-                    b"L" => {
-                        state = State::Nothing;
-                        level_lines.push('\n');
+                        b"Description" => state = State::Description,
+                        b"Email" => state = State::Email,
+                        b"Url" => state = State::Url,
+                        b"Level" => level_lines.clear(),
+                        b"L" => state = State::Line,
+                        _ => {}
                     }
-                    _ => {}
-                },
-
-                Ok(Event::Text(ref e)) => match state {
-                    State::Nothing => {}
-                    State::Line if !parse_levels => {}
-                    _ => {
-                        let s = e.unescape_and_decode(&reader).unwrap();
-                        match state {
-                            State::Title => title.push_str(&s),
-                            State::Description => description.push_str(&s),
-                            State::Email => email.push_str(&s),
-                            State::Url => url.push_str(&s),
-                            State::Line => level_lines.push_str(&s),
-                            _ => unreachable!(),
+                }
+
+                Ok(Event::End(e)) => {
+                    depth -= 1;
+                    match e.name() {
+                        b"Title" | b"Description" | b"Email" | b"Url" => state = State::Nothing,
+                        b"Level" => on_level(RawLevel {
+                            title: None,
+                            content: level_lines.clone(),
+                        })?,
+                        b"L" => {
+                            state = State::Nothing;
+                            level_lines.push('\n');
                         }
+                        _ => {}
                     }
-                },
+                }
 
+                Ok(Event::Text(ref e)) => {
+                    let s = e.unescape_and_decode(&reader)?;
+                    match state {
+                        State::Nothing => {}
+                        State::Title => title.push_str(&s),
+                        State::Description => description.push_str(&s),
+                        State::Email => email.push_str(&s),
+                        State::Url => url.push_str(&s),
+                        State::Line => level_lines.push_str(&s),
+                    }
+                }
+
+                // A truncated file may end without closing all elements
+                Ok(Event::Eof) if depth > 0 => {
+                    return Err(SokobanError::XmlError(format!(
+                        "Unexpected end of file at position {}",
+                        reader.buffer_position()
+                    )));
+                }
                 Ok(Event::Eof) => break,
 
-                Err(e) => panic!("Error at position {}: {:?}", reader.buffer_position(), e),
+                Err(e) => {
+                    return Err(SokobanError::XmlError(format!(
+                        "{} at position {}",
+                        e,
+                        reader.buffer_position()
+                    )));
+                }
                 _ => {}
             }
         }
 
-        Ok(Collection {
-            name: title,
-            short_name: short_name.to_string(),
-            description: if description.is_empty() {
-                None
-            } else {
-                Some(description)
-            },
-            number_of_levels: num,
-            levels,
+        Ok(CollectionMetadata {
+            title,
+            description: non_empty(description),
+            author_email: non_empty(email),
+            url: non_empty(url),
         })
     }
 
     // Accessor methods
     pub fn name(&self) -> &str {
-        &self.name
+        &self.metadata.title
     }
 
     pub fn short_name(&self) -> &str {
@@ -240,24 +468,84 @@ impl Collection {
     }
 
     pub fn description(&self) -> Option<&str> {
-        match self.description {
+        match self.metadata.description {
             Some(ref x) => Some(&x),
             None => None,
         }
     }
 
+    /// Title, description and author information.
+    pub fn metadata(&self) -> &CollectionMetadata {
+        &self.metadata
+    }
+
+    /// The first level of the collection. Panics if no levels have been loaded, e.g. after
+    /// `parse_metadata`.
     pub fn first_level(&self) -> &Level {
         &self.levels[0]
     }
 
+    /// The first level of the collection, or `None` if no levels have been loaded.
+    pub fn first_level_checked(&self) -> Option<&Level> {
+        self.levels.first()
+    }
+
     /// Get all levels.
     pub fn levels(&self) -> &[Level] {
         self.levels.as_ref()
     }
 
+    /// Parse the levels one at a time. This also works if only the metadata of the collection
+    /// has been loaded.
+    pub fn iter_levels(&self) -> impl Iterator<Item = Result<Level, SokobanError>> + '_ {
+        let options = self.options;
+        self.raw_levels
+            .iter()
+            .enumerate()
+            .map(move |(i, raw)| raw.parse(i, options))
+    }
+
+    /// The titles of all levels, using "Level <rank>" for those without a title. Like
+    /// `iter_levels`, this does not require the levels to have been parsed.
+    pub fn level_titles(&self) -> Vec<String> {
+        self.raw_levels
+            .iter()
+            .enumerate()
+            .map(|(i, raw)| {
+                raw.title
+                    .clone()
+                    .unwrap_or_else(|| format!("Level {}", i + 1))
+            })
+            .collect()
+    }
+
     pub fn number_of_levels(&self) -> usize {
         self.number_of_levels
     }
+
+    /// Write the collection in the ASCII format, i.e. the title and description followed by all
+    /// levels, separated by empty lines. Like `iter_levels`, this does not require the levels to
+    /// have been parsed.
+    pub fn to_lvl_string(&self) -> String {
+        let mut result = self.name().to_string();
+        if let Some(description) = self.description() {
+            // An empty line would end the description, so leave those out.
+            for line in description.lines().map(str::trim).filter(|x| !x.is_empty()) {
+                result.push('\n');
+                result.push_str(line);
+            }
+        }
+        for raw in &self.raw_levels {
+            if let Some(ref title) = raw.title {
+                result.push_str("\n\n");
+                result.push_str(title);
+            }
+            result.push_str("\n\n");
+            result.push_str(raw.content.trim_end_matches('\n'));
+        }
+        result.push('\n');
+        result
+    }
 }
 
 #[cfg(test)]
@@ -270,4 +558,294 @@ mod test {
         assert!(Collection::parse("test3iuntrenutineaniutea").is_err());
         assert!(Collection::parse_metadata("test3iuntrenutineaniutea").is_err());
     }
+
+    #[test]
+    fn slc_metadata() {
+        let collection = Collection::parse_metadata("test_2").unwrap();
+        let metadata = collection.metadata();
+        assert_eq!(metadata.title, "Simple collection for testing");
+        assert_eq!(
+            metadata.author_email.as_deref(),
+            Some("yoshio@asahi.email.ne.jp")
+        );
+        assert_eq!(
+            metadata.url.as_deref(),
+            Some("http://www.ne.jp/asahi/ai/yoshio/sokoban/main.htm")
+        );
+        assert!(metadata.description.is_some());
+
+        let collection = Collection::parse_metadata("original").unwrap();
+        assert!(collection.metadata().author_email.is_none());
+        assert!(collection.metadata().url.is_none());
+    }
+
+    #[test]
+    fn malformed_xml() {
+        let xml = "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+                   <SokobanLevels>\n\
+                   <LevelCollection>\n\
+                   <Level Id=\"1\"><L>#####</L><L>#@$.#</L><L>#####</L></Level>\n";
+
+        let options = ParseOptions::default();
+        let complete = format!("{}</LevelCollection>\n</SokobanLevels>\n", xml);
+        let collection =
+            Collection::parse_xml("untitled", complete.as_bytes(), true, options).unwrap();
+        assert_eq!(collection.name(), "untitled");
+        assert_eq!(collection.number_of_levels(), 1);
+
+        assert!(Collection::parse_xml("truncated", xml.as_bytes(), true, options).is_err());
+        let truncated = &xml[..xml.len() - 20];
+        assert!(Collection::parse_xml("truncated", truncated.as_bytes(), true, options).is_err());
+        let mismatched = format!("{}</Level></SokobanLevels>", xml);
+        assert!(
+            Collection::parse_xml("mismatched", mismatched.as_bytes(), false, options).is_err()
+        );
+    }
+
+    #[test]
+    fn parse_xml_level_by_level() {
+        let xml = "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+                   <SokobanLevels>\n\
+                   <Title>Streamed</Title>\n\
+                   <LevelCollection>\n\
+                   <Level Id=\"1\"><L>#####</L><L>#@$.#</L><L>#####</L></Level>\n\
+                   <Level Id=\"2\"><L>######</L><L>#.$ @#</L><L>######</L></Level>\n\
+                   <Level Id=\"3\"><L>######</L><L>#@ $.#</L><L>######</L></Level>\n\
+                   </LevelCollection>\n\
+                   </SokobanLevels>\n";
+
+        let options = ParseOptions::default();
+        let mut columns = vec![];
+        let metadata =
+            Collection::parse_xml_each(xml.as_bytes(), options, |lvl| columns.push(lvl.columns))
+                .unwrap();
+        assert_eq!(metadata.title, "Streamed");
+        assert_eq!(columns, vec![5, 6, 6]);
+
+        let collection = Collection::parse_xml("streamed", xml.as_bytes(), true, options).unwrap();
+        assert_eq!(collection.number_of_levels(), columns.len());
+    }
+
+    #[test]
+    fn lazy_levels() {
+        for &name in &["original", "test_2"] {
+            let eager = Collection::parse(name).unwrap();
+            let lazy = Collection::parse_metadata(name).unwrap();
+            assert!(lazy.levels().is_empty());
+            assert_eq!(lazy.iter_levels().count(), eager.number_of_levels());
+
+            let first = lazy.iter_levels().next().unwrap().unwrap();
+            assert_eq!(first.to_string(), eager.first_level().to_string());
+            assert_eq!(first.title, eager.first_level().title);
+        }
+    }
+
+    #[test]
+    fn metadata_only_has_no_first_level() {
+        let collection = Collection::parse_metadata("test_2").unwrap();
+        assert!(collection.first_level_checked().is_none());
+        assert!(crate::game::Game::new(collection).is_err());
+
+        let collection = Collection::parse("test_2").unwrap();
+        assert!(collection.first_level_checked().is_some());
+    }
+
+    #[test]
+    fn merge_collections() {
+        let first = "First\n\n#####\n#@$.#\n#####\n\n######\n#@$ .#\n######\n";
+        let second = "Second\n\n#####\n#.$@#\n#####\n\n######\n#. $@#\n######\n";
+        let collections = vec![
+            Collection::from_lvl_str("first", first).unwrap(),
+            Collection::from_lvl_str("second", second).unwrap(),
+        ];
+
+        let merged = Collection::merge(collections, "both").unwrap();
+        assert_eq!(merged.name(), "both");
+        assert_eq!(merged.number_of_levels(), 4);
+        let expected = ["#@$.#", "#@$ .#", "#.$@#", "#. $@#"];
+        for (rank, level) in (1..=4).zip(merged.iter_levels()) {
+            let level = level.unwrap();
+            assert_eq!(level.to_string().lines().nth(1), Some(expected[rank - 1]));
+            assert_eq!(merged.levels()[rank - 1].to_string(), level.to_string());
+        }
+
+        let game = crate::game::Game::new(merged).unwrap();
+        assert_eq!(game.rank(), 1);
+    }
+
+    #[test]
+    fn merge_metadata_only() {
+        let lvl = "Single\n\n#####\n#@$.#\n#####\n";
+        let collections = vec![
+            Collection::from_lvl_str("single", lvl).unwrap(),
+            Collection::parse_metadata("test_2").unwrap(),
+        ];
+        let number_of_levels = 1 + collections[1].number_of_levels();
+
+        let merged = Collection::merge(collections, "both").unwrap();
+        assert_eq!(merged.number_of_levels(), number_of_levels);
+        assert!(merged.levels().is_empty());
+        assert_eq!(merged.iter_levels().count(), number_of_levels);
+        assert!(merged.iter_levels().all(|level| level.is_ok()));
+    }
+
+    #[test]
+    fn merge_keeps_options() {
+        let options = ParseOptions {
+            require_goals: true,
+            ..ParseOptions::default()
+        };
+        let merged = Collection::merge(
+            vec![
+                Collection::parse_with_options("test_2", options).unwrap(),
+                Collection::parse_with_options("test_2", options).unwrap(),
+            ],
+            "twice",
+        )
+        .unwrap();
+        assert_eq!(merged.options, options);
+
+        let mixed = vec![
+            Collection::parse_with_options("test_2", options).unwrap(),
+            Collection::parse("test_2").unwrap(),
+        ];
+        assert!(matches!(
+            Collection::merge(mixed, "mixed"),
+            Err(SokobanError::ParseOptionsMismatch(_))
+        ));
+    }
+
+    #[test]
+    fn from_string() {
+        let content = "Two levels\n\n\
+                       #####\n\
+                       #@$.#\n\
+                       #####\n\n\
+                       ######\n\
+                       #.$ @#\n\
+                       ######\n";
+        let collection = Collection::from_lvl_str("two_levels", content).unwrap();
+        assert_eq!(collection.name(), "Two levels");
+        assert_eq!(collection.short_name(), "two_levels");
+        assert_eq!(collection.number_of_levels(), 2);
+        assert_eq!(collection.levels()[0].to_string(), "#####\n#@$.#\n#####");
+        assert_eq!(collection.levels()[1].to_string(), "######\n#.$ @#\n######");
+    }
+
+    #[test]
+    fn level_titles() {
+        let content = "Titles\n\n\
+                       Level 1\n\n\
+                       #####\n#@$.#\n#####\n\n\
+                       ######\n#.$ @#\n######\n\n\
+                       Level 3\n\n\
+                       ######\n#@ $.#\n######\n";
+        let collection = Collection::from_lvl_str("titles", content).unwrap();
+        assert_eq!(collection.number_of_levels(), 3);
+        let titles: Vec<_> = collection
+            .levels()
+            .iter()
+            .map(|lvl| lvl.title.as_deref())
+            .collect();
+        assert_eq!(titles, vec![Some("Level 1"), None, Some("Level 3")]);
+        assert_eq!(collection.levels()[2].to_string(), "######\n#@ $.#\n######");
+
+        let metadata =
+            Collection::parse_lvl("titles", content, false, ParseOptions::default()).unwrap();
+        assert_eq!(metadata.number_of_levels(), 3);
+
+        let converted = Collection::from_lvl_str("titles", &collection.to_lvl_string()).unwrap();
+        assert_eq!(converted.levels()[0].title.as_deref(), Some("Level 1"));
+    }
+
+    #[test]
+    fn level_titles_with_fallback() {
+        let content = "Titles\n\n\
+                       First steps\n\n\
+                       #####\n#@$.#\n#####\n\n\
+                       ######\n#.$ @#\n######\n\n\
+                       The end\n\n\
+                       ######\n#@ $.#\n######\n";
+        let expected = vec!["First steps", "Level 2", "The end"];
+
+        let collection = Collection::from_lvl_str("titles", content).unwrap();
+        assert_eq!(collection.level_titles(), expected);
+
+        let metadata =
+            Collection::parse_lvl("titles", content, false, ParseOptions::default()).unwrap();
+        assert_eq!(metadata.level_titles(), expected);
+    }
+
+    #[test]
+    fn require_goals() {
+        let content = "Decorated\n\n\
+                       #####\n#@$.#\n#####\n\n\
+                       #####\n#@  #\n#####\n";
+        assert!(Collection::from_lvl_str("decorated", content).is_ok());
+
+        let options = ParseOptions {
+            require_goals: true,
+            ..ParseOptions::default()
+        };
+        match Collection::parse_lvl("decorated", content, true, options) {
+            Err(SokobanError::NoGoals(2)) => {}
+            res => panic!("unexpected result: {:?}", res),
+        }
+
+        // Only the metadata is loaded, so the levels are checked when they are parsed later on
+        let metadata = Collection::parse_lvl("decorated", content, false, options).unwrap();
+        let results: Vec<_> = metadata.iter_levels().map(|res| res.is_ok()).collect();
+        assert_eq!(results, vec![true, false]);
+    }
+
+    #[test]
+    fn crlf_and_tabs() {
+        let content = "CRLF\r\n\r\n\
+                       #####\r\n#@$.#\r\n#####\r\n\r\n\
+                       ######\r\n#.$ @#\r\n######\r\n";
+        let collection = Collection::from_lvl_str("crlf", content).unwrap();
+        assert_eq!(collection.name(), "CRLF");
+        assert_eq!(collection.number_of_levels(), 2);
+        assert_eq!(collection.levels()[1].to_string(), "######\n#.$ @#\n######");
+
+        let content = "Tabs\r\n\r\n\
+                       #####\r\n#@$.#\r\n#####\r\n\r\n\
+                       ######\r\n#.$\t@#\r\n######\r\n";
+        let err = Collection::from_lvl_str("tabs", content).unwrap_err();
+        assert_eq!(err.to_string(), "Level #2 contains a tab in line 2");
+    }
+
+    #[test]
+    fn slc_to_lvl() {
+        let collection = Collection::parse("test_2").unwrap();
+        let lvl_string = collection.to_lvl_string();
+        let converted = Collection::from_lvl_str("test_2", &lvl_string).unwrap();
+
+        assert_eq!(converted.name(), collection.name());
+        assert_eq!(converted.number_of_levels(), collection.number_of_levels());
+        for (a, b) in converted.levels().iter().zip(collection.levels()) {
+            assert_eq!(a.to_string(), b.to_string());
+        }
+        assert_eq!(converted.to_lvl_string(), lvl_string);
+        assert_eq!(converted.description(), Some("Nothing interesting."));
+
+        // Exporting does not depend on the levels having been parsed
+        let metadata = Collection::parse_metadata("test_2").unwrap();
+        assert_eq!(metadata.to_lvl_string(), lvl_string);
+    }
+
+    #[test]
+    fn lvl_description() {
+        let content = "Described\nFirst line\n  Second line\n\n#####\n#@$.#\n#####\n";
+        let collection = Collection::from_lvl_str("described", content).unwrap();
+        assert_eq!(collection.name(), "Described");
+        assert_eq!(collection.description(), Some("First line\n  Second line"));
+        assert_eq!(
+            collection.to_lvl_string(),
+            "Described\nFirst line\nSecond line\n\n#####\n#@$.#\n#####\n"
+        );
+
+        let collection = Collection::from_lvl_str("plain", "Plain\n\n#####\n#@$.#\n#####\n");
+        assert_eq!(collection.unwrap().description(), None);
+    }
 }
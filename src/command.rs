@@ -19,6 +19,10 @@ pub enum Movement {
     /// Move one step in the given direction if possible. This may involve pushing a crate.
     Step { direction: Direction },
 
+    /// Move one step in the given direction, pulling the crate behind the worker along if there
+    /// is one.
+    Pull { direction: Direction },
+
     /// Move as far as possible in the given direction without pushing crates.
     WalkTillObstacle { direction: Direction },
 
@@ -39,11 +43,45 @@ pub enum Movement {
     /// Try to push the crate at position `from` to position `to`.
     MoveCrateToTarget { from: Position, to: Position },
 
+    /// Push the crate at position `from` onto the closest empty goal it can reach.
+    PushCrateToNearestGoal { from: Position },
+
+    /// Push the crate at `crate_pos` onto the goal at `goal_pos`.
+    AssignCrateToGoal {
+        crate_pos: Position,
+        goal_pos: Position,
+    },
+
+    /// Walk behind the crate at `crate_pos` and push it exactly one cell in the given direction.
+    NudgeCrate {
+        crate_pos: Position,
+        direction: Direction,
+    },
+
+    /// Put the worker on the given cell, no matter whether it can be reached. Used for testing
+    /// levels while editing them.
+    TeleportWorker { position: Position },
+
     /// Undo the previous move.
     Undo,
 
     /// Redo a move previously undone.
     Redo,
+
+    /// Undo all moves, returning to the initial state of the level.
+    UndoAll,
+
+    /// Redo all moves previously undone.
+    RedoAll,
+
+    /// Undo all moves up to and including the most recent push.
+    UndoToLastPush,
+
+    /// Remember the current state of the level.
+    Checkpoint,
+
+    /// Undo (or redo) moves to return to the state in which the most recent checkpoint was set.
+    RestoreCheckpoint,
 }
 
 #[derive(Debug, Clone)]
@@ -76,6 +114,34 @@ pub enum Macro {
     Execute(Slot),
 }
 
+impl Movement {
+    /// Figure out what a click on `target` should do. Without pushing crates, the worker walks to
+    /// any reachable cell using path finding. Crates can only be pushed along the row or column
+    /// the worker is in.
+    pub fn from_click(worker: Position, target: Position, may_push_crate: bool) -> Option<Self> {
+        let same_row_or_column = target.x == worker.x || target.y == worker.y;
+
+        match (same_row_or_column, may_push_crate) {
+            (true, true) => Some(Movement::PushTowards { position: target }),
+            (false, true) => None,
+            (_, false) => Some(Movement::WalkToPosition { position: target }),
+        }
+    }
+}
+
+/// Select the crate whose id follows `selected` for moving it using the keyboard. Crates are
+/// identified by id rather than position so the selection stays valid no matter whether a push
+/// succeeds. If no crate is selected or the selected id is out of range, select the first crate.
+/// After the last crate, select none.
+pub fn select_next_crate(selected: Option<usize>, number_of_crates: usize) -> Option<usize> {
+    match selected {
+        Some(id) if id + 1 < number_of_crates => Some(id + 1),
+        Some(id) if id < number_of_crates => None,
+        _ if number_of_crates > 0 => Some(0),
+        _ => None,
+    }
+}
+
 impl Command {
     /// Does this command change the collection of macros, i.e. cannot be safely recorded in a
     /// macro?
@@ -102,6 +168,7 @@ impl Command {
         match *self {
             Movement(ref m) => match *m {
                 Step { direction } => direction.to_string(),
+                Pull { direction } => format!("~{}", direction),
                 // TODO Find different formats for the next two cases
                 PushTillObstacle { direction: dir } => format!("_{}", dir),
                 WalkTillObstacle { direction: dir } => format!("_{}", dir),
@@ -111,13 +178,140 @@ impl Command {
                 MoveCrateToTarget { from, to } => {
                     format!("![({},{}),({},{})]", from.x, from.y, to.x, to.y)
                 }
+                PushCrateToNearestGoal { from } => format!("!({},{})", from.x, from.y),
+                AssignCrateToGoal {
+                    crate_pos: from,
+                    goal_pos: to,
+                } => format!("$[({},{}),({},{})]", from.x, from.y, to.x, to.y),
+                NudgeCrate {
+                    crate_pos: pos,
+                    direction,
+                } => format!("%({},{}){}", pos.x, pos.y, direction),
+                TeleportWorker { position: pos } => format!("^({},{})", pos.x, pos.y),
                 Undo => "<".to_string(),
                 Redo => ">".to_string(),
+                UndoAll => "<<".to_string(),
+                RedoAll => ">>".to_string(),
+                UndoToLastPush => "<$".to_string(),
+                Checkpoint => "*".to_string(),
+                RestoreCheckpoint => "<*".to_string(),
             },
             Macro(Execute(slot)) => format!("@{}", slot),
+            LevelManagement(crate::LevelManagement::ResetLevel) => "#".to_string(),
             _ => unreachable!(),
         }
     }
+
+    /// Read a command written by `to_string`. Where several movements share a format, the one
+    /// also used for mouse clicks and keyboard shortcuts is chosen, i.e. `_d` is read as
+    /// `WalkTillObstacle` and `(x, y)` as `WalkToPosition`.
+    pub fn parse(s: &str) -> Result<Command, String> {
+        use crate::Movement::*;
+
+        let s = s.trim();
+        let movement = match s {
+            "<" => Undo,
+            ">" => Redo,
+            "<<" => UndoAll,
+            ">>" => RedoAll,
+            "<$" => UndoToLastPush,
+            "*" => Checkpoint,
+            "<*" => RestoreCheckpoint,
+            "#" => return Ok(Command::LevelManagement(LevelManagement::ResetLevel)),
+            _ => {
+                if let Some(slot) = s.strip_prefix('@') {
+                    let slot = slot
+                        .parse()
+                        .map_err(|_| format!("Invalid macro slot: {}", slot))?;
+                    return Ok(Command::Macro(Macro::Execute(slot)));
+                }
+                parse_movement(s).ok_or_else(|| format!("Invalid command: {}", s))?
+            }
+        };
+
+        Ok(Command::Movement(movement))
+    }
+}
+
+/// Parse any movement consisting of a prefix followed by directions and positions.
+fn parse_movement(s: &str) -> Option<Movement> {
+    use crate::Movement::*;
+
+    let mut chars = s.chars();
+    let prefix = chars.next()?;
+    let rest = chars.as_str();
+
+    let movement = match prefix {
+        '~' => Pull {
+            direction: parse_direction(rest)?,
+        },
+        '_' => WalkTillObstacle {
+            direction: parse_direction(rest)?,
+        },
+        '(' => WalkToPosition {
+            position: parse_position(s, '(', ')')?,
+        },
+        '[' => PushTowards {
+            position: parse_position(s, '[', ']')?,
+        },
+        '!' if rest.starts_with('[') => {
+            let (from, to) = parse_position_pair(rest)?;
+            MoveCrateToTarget { from, to }
+        }
+        '!' => PushCrateToNearestGoal {
+            from: parse_position(rest, '(', ')')?,
+        },
+        '$' => {
+            let (crate_pos, goal_pos) = parse_position_pair(rest)?;
+            AssignCrateToGoal {
+                crate_pos,
+                goal_pos,
+            }
+        }
+        '%' => {
+            let split = rest.rfind(')')? + 1;
+            NudgeCrate {
+                crate_pos: parse_position(&rest[..split], '(', ')')?,
+                direction: parse_direction(&rest[split..])?,
+            }
+        }
+        '^' => TeleportWorker {
+            position: parse_position(rest, '(', ')')?,
+        },
+        _ => Step {
+            direction: parse_direction(s)?,
+        },
+    };
+
+    Some(movement)
+}
+
+fn parse_direction(s: &str) -> Option<Direction> {
+    match s {
+        "l" => Some(Direction::Left),
+        "r" => Some(Direction::Right),
+        "u" => Some(Direction::Up),
+        "d" => Some(Direction::Down),
+        _ => None,
+    }
+}
+
+/// Parse a position such as `(3, 4)`, enclosed in the given pair of brackets.
+fn parse_position(s: &str, open: char, close: char) -> Option<Position> {
+    let (x, y) = s.strip_prefix(open)?.strip_suffix(close)?.split_once(',')?;
+    Some(Position::new(
+        x.trim().parse().ok()?,
+        y.trim().parse().ok()?,
+    ))
+}
+
+/// Parse two positions written as `[(1,2),(3,4)]`.
+fn parse_position_pair(s: &str) -> Option<(Position, Position)> {
+    let inner = s.strip_prefix('[')?.strip_suffix(']')?;
+    let split = inner.find(')')? + 1;
+    let from = parse_position(&inner[..split], '(', ')')?;
+    let to = parse_position(inner[split..].strip_prefix(',')?, '(', ')')?;
+    Some((from, to))
 }
 
 /// Did the player try to move a crate?
@@ -130,5 +324,78 @@ pub struct WithCrate(pub bool);
 pub enum Obstacle {
     Wall,
     Crate,
-    // TODO multiple workers might block each other
+    Worker,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn click_to_movement() {
+        let worker = Position::new(3, 3);
+        let same_row = Position::new(7, 3);
+        let diagonal = Position::new(5, 6);
+
+        match Movement::from_click(worker, same_row, false) {
+            Some(Movement::WalkToPosition { position }) => assert_eq!(position, same_row),
+            m => panic!("unexpected movement: {:?}", m),
+        }
+        match Movement::from_click(worker, diagonal, false) {
+            Some(Movement::WalkToPosition { position }) => assert_eq!(position, diagonal),
+            m => panic!("unexpected movement: {:?}", m),
+        }
+        match Movement::from_click(worker, same_row, true) {
+            Some(Movement::PushTowards { position }) => assert_eq!(position, same_row),
+            m => panic!("unexpected movement: {:?}", m),
+        }
+        assert!(Movement::from_click(worker, diagonal, true).is_none());
+    }
+
+    #[test]
+    fn cycle_crate_selection() {
+        let mut selected = None;
+        for expected in 0..3 {
+            selected = select_next_crate(selected, 3);
+            assert_eq!(selected, Some(expected));
+        }
+        assert_eq!(select_next_crate(selected, 3), None);
+
+        // The selected crate does not exist, e.g. after loading a different level
+        assert_eq!(select_next_crate(Some(7), 3), Some(0));
+        assert_eq!(select_next_crate(None, 0), None);
+    }
+
+    #[test]
+    fn parse_round_trip() {
+        let commands = [
+            "l",
+            "~u",
+            "_d",
+            "(3, 4)",
+            "[5, 1]",
+            "![(1,2),(3,4)]",
+            "!(2,7)",
+            "$[(1,1),(6,1)]",
+            "%(4,2)r",
+            "^(10,3)",
+            "<",
+            ">",
+            "<<",
+            ">>",
+            "<$",
+            "*",
+            "<*",
+            "@3",
+            "#",
+        ];
+        for &s in &commands {
+            let command = Command::parse(s).unwrap();
+            assert_eq!(command.to_string(), s);
+        }
+
+        for &s in &["", "x", "~", "(1,2", "[(1,2)]", "%(1,2)", "@x", "(-1, 2)"] {
+            assert!(Command::parse(s).is_err(), "{:?} should not parse", s);
+        }
+    }
 }
@@ -1,14 +1,23 @@
 pub mod graph;
 pub mod pathfinding;
 
-use std::{collections::{HashMap, HashSet}, fmt, sync::mpsc::Sender};
-
-use crate::command::Obstacle;
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    convert::TryFrom,
+    fmt,
+    sync::atomic::{AtomicBool, Ordering},
+    sync::mpsc::Sender,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use crate::command::{Obstacle, WithCrate};
 use crate::direction::*;
 use crate::event::Event;
 use crate::level::builder::Foreground;
 use crate::level::{Background, Level};
-use crate::move_::Move;
+use crate::move_::{simplify_moves, Displacement, Move};
 use crate::position::*;
 use crate::undo::Undo;
 
@@ -20,13 +29,45 @@ pub struct DynamicEntities {
     /// The number of goals that have to be filled to solve the level
     empty_goals: usize,
 
-    /// Where the worker is at the moment
-    worker_position: Position,
+    /// Where the workers are at the moment. Most levels have exactly one worker.
+    workers: Vec<Position>,
+
+    /// The index of the worker that is moved by all movements.
+    active_worker: usize,
 }
 
 impl DynamicEntities {
+    /// Is there neither a crate nor an inactive worker at the given position?
     pub fn is_empty(&self, position: Position) -> bool {
-        !self.is_crate(position)
+        self.is_free_for(self.active_worker, position)
+    }
+
+    /// Could the worker with the given index step onto `position`, i.e. is there neither a crate
+    /// nor any other worker?
+    fn is_free_for(&self, worker: usize, position: Position) -> bool {
+        !self.is_crate(position) && !self.is_worker_other_than(worker, position)
+    }
+
+    /// Where the active worker is at the moment.
+    fn worker_position(&self) -> Position {
+        self.workers[self.active_worker]
+    }
+
+    fn set_worker_position(&mut self, position: Position) {
+        self.workers[self.active_worker] = position;
+    }
+
+    /// Is one of the workers that are currently not being moved at the given position?
+    fn is_other_worker(&self, pos: Position) -> bool {
+        self.is_worker_other_than(self.active_worker, pos)
+    }
+
+    /// Is any worker but the one with the given index at the given position?
+    fn is_worker_other_than(&self, worker: usize, pos: Position) -> bool {
+        self.workers
+            .iter()
+            .enumerate()
+            .any(|(i, &other)| i != worker && other == pos)
     }
 
     /// Is there a crate at the given position?
@@ -45,28 +86,60 @@ pub struct BackgroundEntities {
 
     /// Positions of cells containing an empty floor
     floor: HashSet<Position>,
+
+    /// Positions of cells covered in ice
+    ice: HashSet<Position>,
 }
 
 impl BackgroundEntities {
     pub fn new(level: &Level) -> Self {
-        let to_position= |i: usize| {
-            Position::new(i % level.columns, i / level.columns)
-        };
-        let walls = level.background.iter().enumerate().filter(|(_, &b)| b == Background::Wall).map(|(i, _)| to_position(i)).collect();
-        let goals = level.background.iter().enumerate().filter(|(_, &b)| b == Background::Goal).map(|(i, _)| to_position(i)).collect();
-        let floor = level.background.iter().enumerate().filter(|(_, &b)| b == Background::Floor).map(|(i, _)| to_position(i)).collect();
-        Self {walls, goals, floor}
+        Self::from_cells(level.columns, &level.background)
+    }
+
+    /// Collect the positions of walls, goals and floor cells from a row-major list of cells.
+    pub fn from_cells(columns: usize, background: &[Background]) -> Self {
+        let to_position = |i: usize| Position::new(i % columns, i / columns);
+        let walls = background
+            .iter()
+            .enumerate()
+            .filter(|(_, &b)| b == Background::Wall)
+            .map(|(i, _)| to_position(i))
+            .collect();
+        let goals = background
+            .iter()
+            .enumerate()
+            .filter(|(_, &b)| b == Background::Goal)
+            .map(|(i, _)| to_position(i))
+            .collect();
+        let floor = background
+            .iter()
+            .enumerate()
+            .filter(|(_, &b)| b == Background::Floor)
+            .map(|(i, _)| to_position(i))
+            .collect();
+        let ice = background
+            .iter()
+            .enumerate()
+            .filter(|(_, &b)| b == Background::Ice)
+            .map(|(i, _)| to_position(i))
+            .collect();
+        Self {
+            walls,
+            goals,
+            floor,
+            ice,
+        }
     }
 
     pub fn is_outside(&self, pos: &Position) -> bool {
-        !self.walls.contains(pos) &&
-        !self.goals.contains(pos) &&
-        !self.floor.contains(pos)
+        !self.walls.contains(pos)
+            && !self.goals.contains(pos)
+            && !self.floor.contains(pos)
+            && !self.ice.contains(pos)
     }
 
     pub fn is_interior(&self, pos: &Position) -> bool {
-        self.goals.contains(pos) ||
-        self.floor.contains(pos)
+        self.goals.contains(pos) || self.floor.contains(pos) || self.ice.contains(pos)
     }
 
     pub fn to_background(&self, pos: &Position) -> Background {
@@ -76,12 +149,86 @@ impl BackgroundEntities {
             Background::Wall
         } else if self.goals.contains(pos) {
             Background::Goal
+        } else if self.ice.contains(pos) {
+            Background::Ice
         } else {
             Background::Empty
         }
     }
 }
 
+/// Stops movements consisting of several steps, e.g. walking to a far away cell, after their
+/// current step. Clones share the same flag, so one can be handed to another thread.
+#[derive(Debug, Clone, Default)]
+pub struct CancelHandle(Arc<AtomicBool>);
+
+impl CancelHandle {
+    /// Stop the movement in progress, if any, after its current step.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    /// Forget requests made while no movement was in progress.
+    fn reset(&self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+
+    /// Has the movement been cancelled since the last call? Resets the flag.
+    fn take(&self) -> bool {
+        self.0.swap(false, Ordering::SeqCst)
+    }
+}
+
+/// A complete copy of the state of a level, including the static background.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LevelSnapshot {
+    pub columns: usize,
+    pub rows: usize,
+
+    /// `columns * rows` cells’ backgrounds in row-major order
+    pub background: Vec<Background>,
+
+    /// Positions of all crates together with their ids
+    pub crates: HashMap<Position, usize>,
+
+    /// Where the worker is
+    pub worker: Position,
+
+    /// Where the remaining workers are, if there is more than one
+    #[serde(default)]
+    pub other_workers: Vec<Position>,
+}
+
+/// What to minimise when pushing a crate to a given cell. Ties are broken using the respective
+/// other quantity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Objective {
+    MinPushes,
+    MinMoves,
+}
+
+/// The kind of cell on the other side of a wall's edge. See `CurrentLevel::wall_edges`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeKind {
+    WallToEmpty,
+    WallToFloor,
+}
+
+/// What a cell looks like from the worker's point of view. See `CurrentLevel::access_map`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessKind {
+    /// A wall or a cell outside the level
+    Wall,
+
+    /// A cell the worker can walk to without pushing any crates
+    ReachableFloor,
+
+    /// An empty cell the worker cannot get to without pushing crates
+    UnreachableFloor,
+
+    Crate,
+}
+
 #[derive(Clone)]
 pub struct CurrentLevel {
     columns: usize,
@@ -91,9 +238,71 @@ pub struct CurrentLevel {
 
     dynamic: DynamicEntities,
 
+    /// The direction the worker most recently moved in. After an undo, this is the direction in
+    /// which the worker was moved back.
+    worker_direction: Direction,
+
     undo: Undo<Move>,
 
+    /// The moves dropped from `undo` because of a limit on its length, as written by
+    /// `Move::to_char`. They cannot be undone anymore, but are still needed to count, save and
+    /// replay everything done since the level was started.
+    forgotten_moves: String,
+
+    /// Has the worker been teleported since the level was started? The moves made since then do
+    /// not start from the initial state, so they cannot be replayed.
+    teleported: bool,
+
+    /// How many moves have been undone so far. Redoing a move does not change this.
+    undos: usize,
+
+    /// How many movement commands have been issued, no matter how many moves each of them
+    /// caused.
+    commands: usize,
+
+    /// Time spent on this level while the timer was running, not counting the current stretch.
+    active_time: Duration,
+
+    /// When the timer was last started, or `None` while it is paused.
+    timer_started: Option<Instant>,
+
+    /// Used to stop the movement currently being performed after the next step.
+    cancel: CancelHandle,
+
+    /// All cells the worker has been on or next to.
+    explored: HashSet<Position>,
+
     listeners: Vec<Sender<Event>>,
+
+    /// Whether movements consisting of several steps should be reported as one
+    /// `Event::BatchMove` rather than individual `MoveWorker` and `MoveCrate` events.
+    batch_moves: bool,
+
+    /// Movements collected while a batch is in progress.
+    batch: RefCell<Option<MoveBatch>>,
+}
+
+/// Worker and crate movements to be sent as a single event.
+#[derive(Clone, Default)]
+struct MoveBatch {
+    worker_id: usize,
+    worker_path: Vec<Position>,
+    crate_moves: Vec<(usize, Position, Position)>,
+}
+
+impl MoveBatch {
+    /// Turn the movements collected so far into an event, leaving the batch empty.
+    fn take_event(&mut self) -> Option<Event> {
+        if self.worker_path.is_empty() && self.crate_moves.is_empty() {
+            None
+        } else {
+            Some(Event::BatchMove {
+                worker_id: self.worker_id,
+                worker_path: std::mem::take(&mut self.worker_path),
+                crate_moves: std::mem::take(&mut self.crate_moves),
+            })
+        }
+    }
 }
 
 /// Parse level and some basic utility functions. None of these change an existing `CurrentLevel`. {{{
@@ -107,15 +316,20 @@ impl CurrentLevel {
     }
 
     pub fn worker_position(&self) -> Position {
-        self.dynamic.worker_position
+        self.dynamic.worker_position()
+    }
+
+    /// The extent of the level's grid.
+    pub fn bounds(&self) -> Bounds {
+        Bounds::new(self.columns, self.rows)
     }
 
     fn index(&self, pos: Position) -> usize {
-        pos.x as usize + pos.y as usize * self.columns()
+        self.bounds().index(pos)
     }
 
     pub fn position(&self, i: usize) -> Position {
-        Position::new(i % self.columns, i / self.columns)
+        self.bounds().position(i)
     }
 
     /// A vector of all neighbours of the cell with the given position that contain neither a wall
@@ -129,7 +343,7 @@ impl CurrentLevel {
     }
 
     fn in_bounds(&self, pos: Position) -> bool {
-        pos.x >= 0 && pos.y >= 0 && pos.x < self.columns() as isize && pos.y < self.rows() as isize
+        self.bounds().contains(pos)
     }
 
     /// Is there a crate at the given position?
@@ -148,7 +362,7 @@ impl CurrentLevel {
 
     /// Is the cell with the given coordinates empty, i.e. could a crate be moved into it?
     fn is_worker(&self, pos: Position) -> bool {
-        pos == self.dynamic.worker_position
+        pos == self.dynamic.worker_position()
     }
 
     /// The cell at the given position is neither empty, nor does it contain a wall.
@@ -162,38 +376,208 @@ impl CurrentLevel {
         self.dynamic.empty_goals == 0
     }
 
+    /// How long the level has been worked on, not counting the time the timer was paused.
+    pub fn active_time(&self) -> Duration {
+        self.active_time_at(Instant::now())
+    }
+
+    fn active_time_at(&self, now: Instant) -> Duration {
+        let current = self
+            .timer_started
+            .map(|start| now.saturating_duration_since(start))
+            .unwrap_or_default();
+        self.active_time + current
+    }
+
+    /// Stop counting time, e.g. while the window is not focused.
+    pub fn pause_timer(&mut self) {
+        self.pause_timer_at(Instant::now());
+    }
+
+    fn pause_timer_at(&mut self, now: Instant) {
+        self.active_time = self.active_time_at(now);
+        self.timer_started = None;
+    }
+
+    /// Continue counting time after `pause_timer` has been called.
+    pub fn resume_timer(&mut self) {
+        self.resume_timer_at(Instant::now());
+    }
+
+    fn resume_timer_at(&mut self, now: Instant) {
+        if self.timer_started.is_none() {
+            self.timer_started = Some(now);
+        }
+    }
+
+    /// Is the level in its initial state, i.e. has every move been undone, if any were made?
+    pub fn is_pristine(&self) -> bool {
+        self.undo.actions_performed == 0 && self.forgotten_moves.is_empty()
+    }
+
     /// How moves were performed to reach the current state?
     pub fn number_of_moves(&self) -> usize {
-        self.undo.number_of_actions()
+        self.forgotten_moves.chars().count() + self.undo.number_of_actions()
     }
 
     /// How many times have crates been moved to reach the current state?
     pub fn number_of_pushes(&self) -> usize {
-        self.undo.count_matches(|x| x.moves_crate)
+        let forgotten_pushes = self
+            .forgotten_moves
+            .chars()
+            .filter(char::is_ascii_uppercase)
+            .count();
+        forgotten_pushes + self.undo.count_matches(|x| x.moves_crate)
+    }
+
+    /// How many box lines were needed to reach the current state? A box line is a sequence of
+    /// pushes moving the same crate in the same direction, possibly with the worker walking around
+    /// in between. A crate sliding across ice ends the current box line. Moves forgotten due to a
+    /// limited undo history are not taken into account.
+    pub fn number_of_box_lines(&self) -> usize {
+        let moves = &self.undo.actions[..self.undo.actions_performed];
+
+        // The direction of the previous push and where it left the crate.
+        let mut previous = None;
+        let mut lines = 0;
+        for mv in moves.iter().filter(|mv| mv.moves_crate) {
+            let crate_move = mv
+                .displacements
+                .iter()
+                .find_map(|displacement| match *displacement {
+                    Displacement::Crate { from, to } => Some((from, to)),
+                    Displacement::Worker { .. } => None,
+                });
+            if let Some((from, to)) = crate_move {
+                if previous != Some((mv.direction, from)) {
+                    lines += 1;
+                }
+                // A crate that slid further than one cell does not continue the box line.
+                previous = if to == from.neighbour(mv.direction) {
+                    Some((mv.direction, to))
+                } else {
+                    None
+                };
+            }
+        }
+        lines
+    }
+
+    /// Only keep the given number of most recent moves in the undo history so very long sessions
+    /// do not use more and more memory. Older moves can no longer be undone, but they are kept in
+    /// a compact form, so they are still counted and saved, and the level can still be solved.
+    pub fn set_max_history(&mut self, max_history: Option<usize>) {
+        self.undo.set_max_history(max_history);
+    }
+
+    /// Can every move made since the level was started still be undone?
+    pub fn has_complete_history(&self) -> bool {
+        self.undo.is_complete()
+    }
+
+    /// Have any crates been pulled to reach the current state? Pulling is not allowed in regular
+    /// Sokoban, so such moves do not count as a solution.
+    pub fn has_pulls(&self) -> bool {
+        let is_pull = |c| matches!(Move::try_from(c), Ok(r#move) if r#move.pulls_crate);
+        self.forgotten_moves.chars().any(is_pull)
+            || self.undo.actions[..self.undo.actions_performed]
+                .iter()
+                .any(|r#move| r#move.pulls_crate)
+    }
+
+    /// Has the worker been teleported since the level was started or restored? If so, the moves
+    /// made so far do not lead to the current state from the initial one.
+    pub fn has_teleported(&self) -> bool {
+        self.teleported
+    }
+
+    /// How many times has a move been undone while solving this level?
+    pub fn number_of_undos(&self) -> usize {
+        self.undos
+    }
+
+    /// How many movement commands, e.g. single steps or walking to a far away cell, have been
+    /// issued so far. Unlike `number_of_moves`, this counts following a path as one command.
+    pub fn number_of_commands(&self) -> usize {
+        self.commands
+    }
+
+    /// Note that a movement command has been issued.
+    pub fn count_command(&mut self) {
+        self.commands += 1;
     }
 
     /// Which direction is the worker currently facing?
     pub fn worker_direction(&self) -> Direction {
-        if self.undo.is_empty() {
-            Direction::Left
-        } else {
-            self.undo.last().direction
-        }
+        self.worker_direction
     }
 
     /// Create a string representation of the moves made to reach the current state.
     pub fn moves_to_string(&self) -> String {
-        self.undo.to_string(Move::to_char)
+        self.forgotten_moves.clone() + &self.undo.to_string(Move::to_char)
+    }
+
+    /// Like `moves_to_string`, but without walking back and forth needlessly. Moves forgotten due
+    /// to a limited undo history are left as they are.
+    pub fn simplified_move_string(&self) -> String {
+        let moves = &self.undo.actions[..self.undo.actions_performed];
+        let simplified = simplify_moves(moves);
+        self.forgotten_moves.clone() + &simplified.iter().map(Move::to_char).collect::<String>()
+    }
+
+    /// All moves performed so far, each with whether it pushed a crate and, if so, where the crate
+    /// came to rest. Moves forgotten due to a limited undo history are not included.
+    pub fn annotated_moves(&self) -> Vec<(Move, bool, Option<Position>)> {
+        self.undo.actions[..self.undo.actions_performed]
+            .iter()
+            .map(|r#move| {
+                let crate_destination = if r#move.moves_crate {
+                    r#move
+                        .displacements
+                        .iter()
+                        .find_map(|displacement| match *displacement {
+                            Displacement::Crate { to, .. } => Some(to),
+                            Displacement::Worker { .. } => None,
+                        })
+                } else {
+                    None
+                };
+                (r#move.clone(), r#move.moves_crate, crate_destination)
+            })
+            .collect()
     }
 
     /// Get an ordered list of the crates’ positions where the id of a crate is its index in the
-    /// list.
+    /// list. As ids are assigned in reading order when parsing a level and never change, the
+    /// crate at `crate_positions()[id]` is always the same one.
     pub fn crate_positions(&self) -> Vec<Position> {
         let mut crates: Vec<_> = self.dynamic.crates.iter().collect();
         crates.sort_by_key(|&(_pos, id)| id);
         crates.into_iter().map(|(&pos, _id)| pos).collect()
     }
 
+    /// The id of the crate at the given position, if there is one. Crates are numbered in the
+    /// order in which they appear in the level description, row by row, and keep their id when
+    /// they are moved.
+    pub fn crate_id_at(&self, pos: Position) -> Option<usize> {
+        self.dynamic.crates.get(&pos).cloned()
+    }
+
+    /// Where the crate with the given id currently is.
+    pub fn crate_position(&self, id: usize) -> Option<Position> {
+        self.dynamic
+            .crates
+            .iter()
+            .find(|&(_, &crate_id)| crate_id == id)
+            .map(|(&pos, _)| pos)
+    }
+
+    /// Is the crate with the given id on a goal? Return `None` if there is no such crate.
+    pub fn crate_on_goal(&self, id: usize) -> Option<bool> {
+        self.crate_position(id)
+            .map(|pos| self.background(pos) == Background::Goal)
+    }
+
     pub fn background(&self, pos: Position) -> Background {
         self.background.to_background(&pos)
     }
@@ -201,6 +585,101 @@ impl CurrentLevel {
     pub fn background_cells(&self) -> Vec<Background> {
         (0..self.columns*self.rows).map(|i| self.background(self.position(i))).collect()
     }
+
+    /// All edges between a wall and a cell that is not a wall, e.g. for drawing transition tiles.
+    /// Each edge is given by the wall's position and the direction in which the other cell lies.
+    /// Cells outside the grid are ignored.
+    pub fn wall_edges(&self) -> Vec<(Position, Direction, EdgeKind)> {
+        let mut result = vec![];
+        for pos in self.bounds().iter() {
+            if self.background(pos) != Background::Wall {
+                continue;
+            }
+            for &direction in &DIRECTIONS {
+                let neighbour = pos.neighbour(direction);
+                if !self.in_bounds(neighbour) {
+                    continue;
+                }
+                let kind = match self.background(neighbour) {
+                    Background::Wall => continue,
+                    Background::Empty => EdgeKind::WallToEmpty,
+                    _ => EdgeKind::WallToFloor,
+                };
+                result.push((pos, direction, kind));
+            }
+        }
+        result
+    }
+
+    /// Where does a crate pushed onto `pos` in the given direction come to rest? A crate on ice
+    /// keeps sliding as long as the next cell is empty ice.
+    fn slide_destination(&self, mut pos: Position, direction: Direction) -> Position {
+        while self.background(pos) == Background::Ice {
+            let next = pos.neighbour(direction);
+            let is_blocked = self.is_crate(next) || self.dynamic.workers.contains(&next);
+            if self.background(next) != Background::Ice || is_blocked {
+                break;
+            }
+            pos = next;
+        }
+        pos
+    }
+
+    /// Copy the full state of the level, without the history of moves.
+    pub fn snapshot(&self) -> LevelSnapshot {
+        LevelSnapshot {
+            columns: self.columns,
+            rows: self.rows,
+            background: self.background_cells(),
+            crates: self.dynamic.crates.clone(),
+            worker: self.dynamic.workers[0],
+            other_workers: self.dynamic.workers[1..].to_vec(),
+        }
+    }
+
+    /// Count the crates which are not on a goal.
+    fn count_empty_goals(&self) -> usize {
+        self.dynamic
+            .crates
+            .keys()
+            .filter(|&&pos| self.background(pos) != Background::Goal)
+            .count()
+    }
+
+    /// All crates that can never be moved again, sorted in reading order. A crate is frozen if
+    /// it can neither be pushed horizontally nor vertically because on at least one side along
+    /// each axis there is a wall or another frozen crate. Frozen crates on goals are harmless,
+    /// but a frozen crate anywhere else means the level can no longer be solved.
+    pub fn frozen_crates(&self) -> Vec<Position> {
+        // Start by assuming every crate is frozen and release those that have room to move
+        // until nothing changes any more.
+        let mut frozen: HashSet<Position> = self.dynamic.crates.keys().cloned().collect();
+        let blocked = |frozen: &HashSet<Position>, pos: Position| {
+            !self.is_interior(pos) || frozen.contains(&pos)
+        };
+
+        loop {
+            let movable: Vec<_> = frozen
+                .iter()
+                .cloned()
+                .filter(|&pos| {
+                    let horizontal = blocked(&frozen, pos.left()) || blocked(&frozen, pos.right());
+                    let vertical = blocked(&frozen, pos.above()) || blocked(&frozen, pos.below());
+                    !(horizontal && vertical)
+                })
+                .collect();
+            if movable.is_empty() {
+                break;
+            }
+            for pos in movable {
+                frozen.remove(&pos);
+            }
+        }
+
+        let mut result: Vec<_> = frozen.into_iter().collect();
+        result.sort_by_key(|p| (p.y, p.x));
+        result
+    }
 }
 // }}}
 
@@ -211,52 +690,193 @@ impl CurrentLevel {
     }
 
     fn notify(&self, event: &Event) {
+        // While batching, collect movements. Any other event except for newly revealed cells
+        // has to be sent after the movements preceding it.
+        let batched_moves = match (self.batch.borrow_mut().as_mut(), event) {
+            (Some(batch), &Event::MoveWorker { worker_id, to, .. }) => {
+                batch.worker_id = worker_id;
+                batch.worker_path.push(to);
+                return;
+            }
+            (Some(batch), &Event::MoveCrate { id, from, to }) => {
+                batch.crate_moves.push((id, from, to));
+                return;
+            }
+            (Some(_), &Event::CellsRevealed { .. }) | (None, _) => None,
+            (Some(batch), _) => batch.take_event(),
+        };
+
+        if let Some(batched_moves) = batched_moves {
+            self.send(&batched_moves);
+        }
+        self.send(event);
+    }
+
+    fn send(&self, event: &Event) {
         for sender in &self.listeners {
             sender.send(event.clone()).unwrap();
         }
     }
 
-    fn move_worker_from_to(&mut self, from_to: FromTo) -> Event {
+    /// Choose whether movements consisting of several steps should be reported as a single
+    /// `Event::BatchMove`.
+    pub fn set_batch_moves(&mut self, batch_moves: bool) {
+        self.batch_moves = batch_moves;
+    }
+
+    /// Start collecting movements if batching is enabled and no batch is in progress yet.
+    /// Return true if and only if a new batch was started.
+    fn begin_batch(&self) -> bool {
+        let mut batch = self.batch.borrow_mut();
+        if self.batch_moves && batch.is_none() {
+            *batch = Some(MoveBatch::default());
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Send the movements collected since `begin_batch`.
+    fn end_batch(&self) {
+        let batched_moves = self
+            .batch
+            .borrow_mut()
+            .take()
+            .and_then(|mut b| b.take_event());
+        if let Some(batched_moves) = batched_moves {
+            self.send(&batched_moves);
+        }
+    }
+
+    /// Put the worker on an arbitrary interior cell that does not contain a crate. As the worker
+    /// may not be able to get there by walking, the history of moves is cleared and the level no
+    /// longer yields a solution until it is reset. Return true if and only if the worker was moved.
+    pub fn teleport_worker(&mut self, to: Position) -> bool {
+        let obstacle = if !self.is_interior(to) {
+            Obstacle::Wall
+        } else if self.is_crate(to) {
+            Obstacle::Crate
+        } else if self.dynamic.is_other_worker(to) {
+            Obstacle::Worker
+        } else {
+            let worker = self.dynamic.active_worker;
+            let event = self.move_worker_to(worker, to, self.worker_direction);
+            self.undo.clear();
+            self.forgotten_moves.clear();
+            self.teleported = true;
+            self.notify(&event);
+            return true;
+        };
+        self.notify(&Event::CannotMove(WithCrate(false), obstacle));
+        false
+    }
+
+    /// Choose which worker is moved by subsequent movements. Return false if there is no worker
+    /// with the given id.
+    ///
+    /// TODO Strings of moves, e.g. those stored in save files, do not say which worker moved, so
+    /// levels with several workers cannot be saved and loaded yet.
+    pub fn select_worker(&mut self, id: usize) -> bool {
+        if id < self.dynamic.workers.len() {
+            self.dynamic.active_worker = id;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Replace the state of the level by the given snapshot and clear the history of moves. As
+    /// the level does not know its own rank, it has to be passed in for the `InitialLevelState`
+    /// event.
+    pub fn restore(&mut self, snapshot: LevelSnapshot, rank: usize) {
+        let LevelSnapshot {
+            columns,
+            rows,
+            background,
+            crates,
+            worker,
+            other_workers,
+        } = snapshot;
+
+        self.columns = columns;
+        self.rows = rows;
+        self.background = BackgroundEntities::from_cells(columns, &background);
+        self.dynamic.crates = crates.clone();
+        self.dynamic.workers = Some(worker).into_iter().chain(other_workers).collect();
+        self.dynamic.active_worker = 0;
+        self.dynamic.empty_goals = self.count_empty_goals();
+        self.worker_direction = Direction::Left;
+        self.undo.clear();
+        self.forgotten_moves.clear();
+        self.teleported = false;
+        self.undos = 0;
+        self.active_time = Duration::default();
+        self.timer_started = Some(Instant::now());
+        self.explored.clear();
+        self.reveal_around(worker);
+
+        self.notify(&Event::InitialLevelState {
+            rank,
+            columns,
+            rows,
+            background,
+            worker_position: worker,
+            worker_direction: self.worker_direction,
+            crates,
+        });
+    }
+
+    fn move_worker_from_to(&mut self, worker: usize, from_to: FromTo) -> Event {
         let FromTo { from, to } = from_to;
         if let DirectionResult::Neighbour { direction } = direction(from, to) {
-            self.move_worker_to(to, direction)
+            self.move_worker_to(worker, to, direction)
         } else {
             panic!("invalid FromTo: {:?}", from_to)
         }
     }
 
     fn move_worker(&mut self, direction: Direction) -> Event {
-        let to = self.dynamic.worker_position.neighbour(direction);
-        self.move_worker_to(to, direction)
+        let to = self.dynamic.worker_position().neighbour(direction);
+        self.move_worker_to(self.dynamic.active_worker, to, direction)
     }
 
-    fn move_worker_back(&mut self, direction: Direction) -> Event {
-        let to = self.dynamic.worker_position.neighbour(direction.reverse());
-        let from = self.dynamic.worker_position;
-        self.dynamic.worker_position = to;
+    /// Cells the worker has been on or next to since the level was started.
+    pub fn explored_cells(&self) -> &HashSet<Position> {
+        &self.explored
+    }
 
-        Event::MoveWorker {
-            from,
-            to,
-            direction,
-        }
+    /// Add the given position and its neighbours to the explored cells. Return those that had not
+    /// been explored before, ordered by row and column.
+    fn reveal_around(&mut self, pos: Position) -> Vec<Position> {
+        let mut revealed: Vec<_> = Some(pos)
+            .into_iter()
+            .chain(DIRECTIONS.iter().map(|&dir| pos.neighbour(dir)))
+            .filter(|&p| self.in_bounds(p) && self.explored.insert(p))
+            .collect();
+        revealed.sort_by_key(|p| (p.y, p.x));
+        revealed
     }
 
-    fn move_worker_to(&mut self, to: Position, direction: Direction) -> Event {
-        let from = self.dynamic.worker_position;
-        self.dynamic.worker_position = to;
+    /// Move the worker with the given index and return the corresponding event. If this reveals
+    /// any cells, the listeners are told about that right away.
+    fn move_worker_to(&mut self, worker: usize, to: Position, direction: Direction) -> Event {
+        let from = self.dynamic.workers[worker];
+        self.dynamic.workers[worker] = to;
+        self.worker_direction = direction;
+
+        let positions = self.reveal_around(to);
+        if !positions.is_empty() {
+            self.notify(&Event::CellsRevealed { positions });
+        }
 
         Event::MoveWorker {
+            worker_id: worker,
             from,
             to,
             direction,
         }
     }
 
-    fn move_crate(&mut self, from: Position, direction: Direction) -> Event {
-        self.move_crate_to(from, from.neighbour(direction))
-    }
-
     // NOTE We need `from` so we can find out the crate's id. That way, the user interface knows
     // which crate to animate. Alternatively, the crate's id could be passed in.
     fn move_crate_to(&mut self, from: Position, to: Position) -> Event {
@@ -306,6 +926,17 @@ pub struct FailedMove {
     pub thing_blocked: BlockedEntity,
 }
 
+/// Why a list of moves stored for a level could not be loaded.
+#[derive(Debug, thiserror::Error)]
+#[cfg_attr(test, derive(PartialEq))]
+pub enum InvalidMoves {
+    #[error("Invalid character '{0}' in list of moves")]
+    InvalidCharacter(char),
+
+    #[error("Move #{index} ('{chr}') cannot be performed")]
+    IllegalMove { index: usize, chr: char },
+}
+
 /// Public movement functions.
 impl CurrentLevel {
     /// Take one step in the specified direction, pushing a crate if necessary.
@@ -324,7 +955,7 @@ impl CurrentLevel {
         let mut moves = vec![];
 
         loop {
-            let next_position = dynamic.worker_position.neighbour(direction);
+            let next_position = dynamic.worker_position().neighbour(direction);
 
             if !self.is_empty(next_position) {
                 break;
@@ -332,18 +963,39 @@ impl CurrentLevel {
 
             moves.push(VerifiedMove {
                 worker_move: FromTo {
-                    from: dynamic.worker_position,
+                    from: dynamic.worker_position(),
                     to: next_position,
                 },
                 crate_move: None,
             });
 
-            dynamic.worker_position = next_position;
+            dynamic.set_worker_position(next_position);
         }
 
         Ok(moves)
     }
 
+    /// How many steps can the worker walk in the given direction without pushing a crate, and
+    /// what stops it?
+    pub fn obstacle_in_direction(&self, direction: Direction) -> (usize, Obstacle) {
+        let mut pos = self.dynamic.worker_position();
+        let mut steps = 0;
+        while self.is_empty(pos.neighbour(direction)) {
+            pos = pos.neighbour(direction);
+            steps += 1;
+        }
+
+        let next = pos.neighbour(direction);
+        let obstacle = if self.is_crate(next) {
+            Obstacle::Crate
+        } else if self.dynamic.is_other_worker(next) {
+            Obstacle::Worker
+        } else {
+            Obstacle::Wall
+        };
+        (steps, obstacle)
+    }
+
     /// Push the crate in the given direction until it hits an obstacle. Needs a crate next to the
     /// worker in the given direction.
     pub fn push_to_obstacle(
@@ -354,7 +1006,7 @@ impl CurrentLevel {
         let mut moves = self.walk_to_obstacle(direction, dynamic)?;
 
         loop {
-            let crate_position = dynamic.worker_position.neighbour(direction);
+            let crate_position = dynamic.worker_position().neighbour(direction);
             if !self.is_crate(crate_position) {
                 break;
             }
@@ -366,7 +1018,7 @@ impl CurrentLevel {
 
             moves.push(VerifiedMove {
                 worker_move: FromTo {
-                    from: dynamic.worker_position,
+                    from: dynamic.worker_position(),
                     to: crate_position,
                 },
                 crate_move: Some(FromTo {
@@ -375,7 +1027,7 @@ impl CurrentLevel {
                 }),
             });
 
-            dynamic.worker_position = crate_position;
+            dynamic.set_worker_position(crate_position);
         }
 
         Ok(moves)
@@ -389,7 +1041,7 @@ impl CurrentLevel {
         dynamic: &mut DynamicEntities,
     ) -> Result<Vec<VerifiedMove>, FailedMove> {
         let direction = if let DirectionResult::Neighbour { direction } =
-            direction(dynamic.worker_position, target_position)
+            direction(dynamic.worker_position(), target_position)
         {
             direction
         } else {
@@ -397,8 +1049,8 @@ impl CurrentLevel {
         };
         let mut moves = vec![];
 
-        while dynamic.worker_position != target_position {
-            let next_position = dynamic.worker_position.neighbour(direction);
+        while dynamic.worker_position() != target_position {
+            let next_position = dynamic.worker_position().neighbour(direction);
 
             if !self.is_empty(next_position) {
                 break;
@@ -406,17 +1058,17 @@ impl CurrentLevel {
 
             moves.push(VerifiedMove {
                 worker_move: FromTo {
-                    from: dynamic.worker_position,
+                    from: dynamic.worker_position(),
                     to: next_position,
                 },
                 crate_move: None,
             });
 
-            dynamic.worker_position = next_position;
+            dynamic.set_worker_position(next_position);
         }
 
-        while dynamic.worker_position != target_position {
-            let crate_position = dynamic.worker_position.neighbour(direction);
+        while dynamic.worker_position() != target_position {
+            let crate_position = dynamic.worker_position().neighbour(direction);
             if !self.is_crate(crate_position) {
                 break;
             }
@@ -428,7 +1080,7 @@ impl CurrentLevel {
 
             moves.push(VerifiedMove {
                 worker_move: FromTo {
-                    from: dynamic.worker_position,
+                    from: dynamic.worker_position(),
                     to: crate_position,
                 },
                 crate_move: Some(FromTo {
@@ -437,7 +1089,7 @@ impl CurrentLevel {
                 }),
             });
 
-            dynamic.worker_position = crate_position;
+            dynamic.set_worker_position(crate_position);
         }
 
         Ok(moves)
@@ -458,55 +1110,84 @@ impl CurrentLevel {
 
     fn perform_move(&mut self, r#move: &Move, record_move: bool) -> Result<Vec<Event>, FailedMove> {
         // DEBT get rid of record_move!
+        let (events, displacements) = self.apply_move(r#move)?;
+
+        if record_move {
+            let forgotten = self.undo.record(Move {
+                displacements,
+                ..r#move.to_owned()
+            });
+            self.forgotten_moves
+                .extend(forgotten.iter().map(Move::to_char));
+        }
+
+        Ok(events)
+    }
+
+    /// Perform a move without recording it. Return the resulting events as well as everything
+    /// that was moved.
+    fn apply_move(&mut self, r#move: &Move) -> Result<(Vec<Event>, Vec<Displacement>), FailedMove> {
         let VerifiedMove {
             worker_move,
             crate_move,
-        } = self.evaluate_move(r#move)?;
+        } = self.evaluate_move(r#move.worker, r#move)?;
 
         let mut events = vec![];
+        let mut displacements = vec![];
         if let Some(FromTo { from, to }) = crate_move {
             events.push(self.move_crate_to(from, to));
+            displacements.push(Displacement::Crate { from, to });
         }
 
-        events.push(self.move_worker_from_to(worker_move));
-
-        if record_move {
-            self.undo.record(r#move.to_owned());
-        }
+        displacements.push(Displacement::Worker {
+            id: r#move.worker,
+            from: worker_move.from,
+            to: worker_move.to,
+        });
+        events.push(self.move_worker_from_to(r#move.worker, worker_move));
 
-        Ok(events)
+        Ok((events, displacements))
     }
 
-    /// Figure out whether a `Move` can be performed at the current state. If so, return what
-    /// changes it causes. Otherwise, return why it cannot be performed.
-    fn evaluate_move(&self, r#move: &Move) -> Result<VerifiedMove, FailedMove> {
+    /// Figure out whether the worker with the given index can perform a `Move` at the current
+    /// state. If so, return what changes it causes. Otherwise, return why it cannot be performed.
+    fn evaluate_move(&self, worker: usize, r#move: &Move) -> Result<VerifiedMove, FailedMove> {
         let dynamic = &self.dynamic;
+        let worker_position = dynamic.workers[worker];
 
         let Move {
             moves_crate,
             direction,
+            pulls_crate,
+            ..
         } = r#move;
-        let new_worker_position = dynamic.worker_position.neighbour(*direction);
+        let new_worker_position = worker_position.neighbour(*direction);
 
         let is_crate = self.is_crate(new_worker_position);
 
         if is_crate && *moves_crate {
             let new_crate_position = new_worker_position.neighbour(*direction);
 
-            if self.is_interior(new_worker_position) && self.is_interior(new_crate_position) && dynamic.is_empty(new_crate_position) {
+            if self.is_interior(new_worker_position)
+                && self.is_interior(new_crate_position)
+                && dynamic.is_free_for(worker, new_crate_position)
+            {
                 Ok(VerifiedMove {
                     worker_move: FromTo {
-                        from: dynamic.worker_position,
+                        from: worker_position,
                         to: new_worker_position,
                     },
                     crate_move: Some(FromTo {
                         from: new_worker_position,
-                        to: new_crate_position,
+                        to: self.slide_destination(new_crate_position, *direction),
                     }),
                 })
             } else {
                 let obstacle = match self.background(new_crate_position) {
                     Background::Wall => Obstacle::Wall,
+                    _ if dynamic.is_worker_other_than(worker, new_crate_position) => {
+                        Obstacle::Worker
+                    }
                     _ => Obstacle::Crate,
                 };
 
@@ -516,17 +1197,39 @@ impl CurrentLevel {
                     thing_blocked: BlockedEntity::Crate,
                 })
             }
-        } else if self.is_interior(new_worker_position) && dynamic.is_empty(new_worker_position) {
+        } else if self.is_interior(new_worker_position)
+            && dynamic.is_free_for(worker, new_worker_position)
+        {
+            let crate_move = if *pulls_crate {
+                let crate_position = worker_position.neighbour(direction.reverse());
+                if !self.is_crate(crate_position) {
+                    // There is nothing to pull
+                    return Err(FailedMove {
+                        obstacle_at: crate_position,
+                        obstacle_type: Obstacle::Crate,
+                        thing_blocked: BlockedEntity::Crate,
+                    });
+                }
+                Some(FromTo {
+                    from: crate_position,
+                    to: worker_position,
+                })
+            } else {
+                None
+            };
+
             Ok(VerifiedMove {
                 worker_move: FromTo {
-                    from: dynamic.worker_position,
+                    from: worker_position,
                     to: new_worker_position,
                 },
-                crate_move: None,
+                crate_move,
             })
         } else {
             let obstacle_type = if is_crate {
                 Obstacle::Crate
+            } else if dynamic.is_worker_other_than(worker, new_worker_position) {
+                Obstacle::Worker
             } else {
                 Obstacle::Wall
             };
@@ -545,16 +1248,12 @@ impl CurrentLevel {
         direction: Direction,
         may_push_crate: bool,
     ) -> Result<(), FailedMove> {
-        let target_position = self.dynamic.worker_position.neighbour(direction);
+        let target_position = self.dynamic.worker_position().neighbour(direction);
         let is_crate = self.dynamic.crates.contains_key(&target_position);
 
-        let events = self.perform_move(
-            &Move {
-                direction,
-                moves_crate: may_push_crate && is_crate,
-            },
-            true,
-        )?;
+        let r#move = Move::new(direction, may_push_crate && is_crate);
+        let r#move = r#move.by_worker(self.dynamic.active_worker);
+        let events = self.perform_move(&r#move, true)?;
         // FIXME properly handle errors
 
         for event in events {
@@ -566,11 +1265,48 @@ impl CurrentLevel {
 
     /// Move the worker towards `to`. If may_push_crate is set, `to` must be in the same row or
     /// column as the worker. In that case, the worker moves to `to`
-    pub fn move_to(&mut self, to: Position, may_push_crate: bool) -> Option<()> {
-        let dir = direction(self.dynamic.worker_position, to);
+    ///
+    /// If `final_facing` is given and the worker actually reaches `to`, the worker is turned to
+    /// face that direction afterwards without moving.
+    pub fn move_to(
+        &mut self,
+        to: Position,
+        may_push_crate: bool,
+        final_facing: Option<Direction>,
+    ) -> Option<()> {
+        self.cancel.reset();
+        let batching = self.begin_batch();
+        let result = self.move_to_helper(to, may_push_crate);
+        if result.is_some() {
+            self.turn_after_moving(to, final_facing);
+        }
+        if batching {
+            self.end_batch();
+        }
+        result
+    }
+
+    /// Turn the worker to face `final_facing` if it is at `to`.
+    fn turn_after_moving(&mut self, to: Position, final_facing: Option<Direction>) {
+        let position = self.dynamic.worker_position();
+        if let Some(direction) = final_facing {
+            if position == to && direction != self.worker_direction {
+                self.worker_direction = direction;
+                self.notify(&Event::MoveWorker {
+                    worker_id: self.dynamic.active_worker,
+                    from: position,
+                    to: position,
+                    direction,
+                });
+            }
+        }
+    }
+
+    fn move_to_helper(&mut self, to: Position, may_push_crate: bool) -> Option<()> {
+        let dir = direction(self.dynamic.worker_position(), to);
 
         if !may_push_crate {
-            let (dx, dy) = to - self.dynamic.worker_position;
+            let (dx, dy) = to - self.dynamic.worker_position();
             if dx.abs() + dy.abs() > 1 {
                 let path = self.find_path(to)?;
                 self.follow_path(path);
@@ -583,7 +1319,10 @@ impl CurrentLevel {
                 // Note that this takes care of both movements of just one step and all cases
                 // in which crates may be pushed.
                 while self.move_helper(direction, may_push_crate).is_ok() {
-                    if self.dynamic.worker_position == to || may_push_crate && self.is_finished() {
+                    if self.dynamic.worker_position() == to
+                        || may_push_crate && self.is_finished()
+                        || self.is_cancelled()
+                    {
                         break;
                     }
                 }
@@ -595,11 +1334,53 @@ impl CurrentLevel {
         Some(())
     }
 
+    /// A handle for stopping movements consisting of several steps, e.g. walking to a far away
+    /// position, from another thread.
+    pub fn cancel_handle(&self) -> CancelHandle {
+        self.cancel.clone()
+    }
+
+    /// Use the given handle for cancelling movements instead of the current one, e.g. so that a
+    /// handle given out earlier keeps working after loading a different level.
+    pub fn set_cancel_handle(&mut self, cancel: CancelHandle) {
+        self.cancel = cancel;
+    }
+
+    /// Check whether the movement in progress should be stopped. If so, tell the listeners and
+    /// reset the flag.
+    fn is_cancelled(&self) -> bool {
+        let cancelled = self.cancel.take();
+        if cancelled {
+            self.notify(&Event::MovementCancelled);
+        }
+        cancelled
+    }
+
     /// Try to move in the given direction. Return an error if that is not possible.
     pub fn try_move(&mut self, direction: Direction) -> Result<(), FailedMove> {
         self.move_helper(direction, true)
     }
 
+    /// Step in the given direction, pulling the crate behind the worker along if there is one.
+    /// Return an error if that is not possible.
+    pub fn pull(&mut self, direction: Direction) -> Result<(), FailedMove> {
+        let behind = self
+            .dynamic
+            .worker_position()
+            .neighbour(direction.reverse());
+        let r#move = if self.is_crate(behind) {
+            Move::pull(direction)
+        } else {
+            Move::new(direction, false)
+        }
+        .by_worker(self.dynamic.active_worker);
+
+        for event in self.perform_move(&r#move, true)? {
+            self.notify(&event);
+        }
+        Ok(())
+    }
+
     /// Move the crate located at `from` to `to` if that is possible.
     pub fn move_crate_to_target(&mut self, from: Position, to: Position) -> Option<()> {
         let path = self.find_path_with_crate(from, to)?;
@@ -607,12 +1388,70 @@ impl CurrentLevel {
         self.push_crate_along_path(path)
     }
 
-    /// Move as far as possible in the given direction (without pushing crates if `may_push_crate`
+    /// Move the crate located at `from` to `to` using the least number of pushes or moves,
+    /// including the steps the worker needs to get behind the crate for each push.
+    pub fn move_crate_to_target_optimal(
+        &mut self,
+        from: Position,
+        to: Position,
+        objective: Objective,
+    ) -> Option<()> {
+        let path = self.find_optimal_path_with_crate(from, to, objective)?;
+
+        self.push_crate_along_path(path)
+    }
+
+    /// Push the crate located at `from` onto the empty goal which can be reached with the least
+    /// number of pushes. Emit `Event::NoPathFound` if there is no such goal.
+    pub fn push_crate_to_nearest_goal(&mut self, from: Position) -> Option<()> {
+        let nearest = self
+            .background
+            .goals
+            .iter()
+            .filter(|&&goal| self.is_empty(goal))
+            .filter_map(|&goal| Some((goal, self.find_path_with_crate(from, goal)?)))
+            .min_by_key(|(goal, path)| (path.steps.len(), goal.y, goal.x));
+
+        if let Some((_, path)) = nearest {
+            self.push_crate_along_path(path)
+        } else {
+            self.notify(&Event::NoPathFound);
+            None
+        }
+    }
+
+    /// Push the crate located at `crate_pos` onto the goal at `goal_pos`. Unlike
+    /// `move_crate_to_target`, this requires the target to be a goal. Emit `Event::NoPathFound`
+    /// if the crate cannot be pushed there.
+    pub fn assign_crate_to_goal(&mut self, crate_pos: Position, goal_pos: Position) -> Option<()> {
+        if !self.background.goals.contains(&goal_pos) {
+            warn!(
+                "Cannot assign crate to ({},{}): not a goal",
+                goal_pos.x, goal_pos.y
+            );
+            return None;
+        }
+
+        if let Some(path) = self.find_path_with_crate(crate_pos, goal_pos) {
+            self.push_crate_along_path(path)
+        } else {
+            self.notify(&Event::NoPathFound);
+            None
+        }
+    }
+
+    /// Move as far as possible in the given direction (without pushing crates if `may_push_crate`
     /// is `false`).
     pub fn move_as_far_as_possible(&mut self, direction: Direction, may_push_crate: bool) {
+        self.cancel.reset();
+        let batching = self.begin_batch();
         while self.move_helper(direction, may_push_crate).is_ok()
             && !(may_push_crate && self.is_finished())
+            && !self.is_cancelled()
         {}
+        if batching {
+            self.end_batch();
+        }
     }
 
     /// Undo the most recent move.
@@ -622,17 +1461,25 @@ impl CurrentLevel {
                 self.notify(&Event::NothingToUndo);
                 false
             }
-            Some(&Move {
-                direction,
-                moves_crate,
-            }) => {
-                let crate_pos = self.dynamic.worker_position.neighbour(direction);
-
-                let event = self.move_worker_back(direction);
-                self.notify(&event);
-
-                if moves_crate {
-                    let event = self.move_crate(crate_pos, direction.reverse());
+            Some(r#move) => {
+                let direction = r#move.direction.reverse();
+                let displacements = r#move.displacements.clone();
+                self.undos += 1;
+
+                // Reverse everything the move did. This also takes care of crates that slid
+                // across ice after being pushed. Crates are moved before the worker, so events
+                // arrive in the same order as when performing a move.
+                let (crates, workers): (Vec<_>, Vec<_>) = displacements
+                    .into_iter()
+                    .rev()
+                    .partition(|displacement| matches!(displacement, Displacement::Crate { .. }));
+                for displacement in crates.into_iter().chain(workers) {
+                    let event = match displacement {
+                        Displacement::Worker { id, from, .. } => {
+                            self.move_worker_to(id, from, direction)
+                        }
+                        Displacement::Crate { from, to } => self.move_crate_to(to, from),
+                    };
                     self.notify(&event);
                 }
 
@@ -641,6 +1488,46 @@ impl CurrentLevel {
         }
     }
 
+    /// Undo moves until a push has been undone, or until there is nothing left to undo.
+    pub fn undo_to_last_push(&mut self) {
+        loop {
+            let moves_crate = self.undo.actions[..self.undo.actions_performed]
+                .last()
+                .map(|r#move| r#move.moves_crate);
+            if !self.undo() || moves_crate == Some(true) {
+                break;
+            }
+        }
+    }
+
+    /// Remember the current point in the history of moves so the player can return here later
+    /// using `restore_checkpoint`.
+    pub fn set_checkpoint(&mut self) {
+        self.undo.set_checkpoint();
+    }
+
+    /// Undo moves until the number of moves is the same as when the checkpoint was set. If moves
+    /// have been undone past the checkpoint, redo them instead. Return false if there is no
+    /// checkpoint or it cannot be reached, e.g. because different moves have been made after
+    /// undoing past it or because it is older than the kept history.
+    pub fn restore_checkpoint(&mut self) -> bool {
+        let checkpoint = if let Some(checkpoint) = self.undo.checkpoint() {
+            checkpoint
+        } else {
+            return false;
+        };
+
+        while self.undo.actions_performed > checkpoint {
+            self.undo();
+        }
+        while self.undo.actions_performed < checkpoint {
+            if !self.redo() {
+                return false;
+            }
+        }
+        true
+    }
+
     /// If a move has been undone previously, redo it.
     pub fn redo(&mut self) -> bool {
         let r#move = if let Some(r#move) = self.undo.redo() {
@@ -650,8 +1537,13 @@ impl CurrentLevel {
             return false;
         };
 
-        match self.perform_move(&r#move, false) {
-            Ok(events) => {
+        match self.apply_move(&r#move) {
+            Ok((events, displacements)) => {
+                // Moves loaded from a file have not been performed before, so remember what the
+                // move did in order to be able to undo it.
+                let performed = self.undo.actions_performed;
+                self.undo.actions[performed - 1].displacements = displacements;
+
                 for event in events {
                     self.notify(&event);
                 }
@@ -664,22 +1556,95 @@ impl CurrentLevel {
         }
     }
 
+    /// Undo or redo moves until exactly `target_moves` moves have been performed. The target is
+    /// clamped to the number of moves that can be redone.
+    pub fn seek(&mut self, target_moves: usize) {
+        let forgotten = self.forgotten_moves.chars().count();
+        let target_moves = target_moves.min(forgotten + self.undo.actions.len());
+        while self.number_of_moves() > target_moves {
+            if !self.undo() {
+                break;
+            }
+        }
+        while self.number_of_moves() < target_moves {
+            if !self.redo() {
+                break;
+            }
+        }
+    }
+
     /// Given a number of simple moves, i.e. up, down, left, right, as a string, execute the first
     /// `number_of_moves` of them. If there are more moves than that, they can be executed using
     /// redo.
     ///
+    /// All moves, including those left for redoing, are checked before anything is changed, so
+    /// the level is left untouched if an error is returned.
+    ///
     /// Used for loading a level.
-    pub fn execute_moves(&mut self, number_of_moves: usize, moves: &str) -> Result<(), FailedMove> {
+    pub fn execute_moves(
+        &mut self,
+        number_of_moves: usize,
+        moves: &str,
+    ) -> Result<(), InvalidMoves> {
         // DEBT Should be moved somewhere else. load.rs, maybe?
-        let moves = crate::move_::parse(moves).unwrap();
-        // TODO Error handling
+        let worker = self.dynamic.active_worker;
+        let moves: Vec<_> = crate::move_::parse(moves)
+            .map_err(InvalidMoves::InvalidCharacter)?
+            .into_iter()
+            .map(|r#move| r#move.by_worker(worker))
+            .collect();
+        self.validate_moves(&moves)?;
+
+        let backup = self.clone();
         for (i, move_) in moves.iter().enumerate() {
             // Some moves might have been undone, so we do not redo them just now.
             if i >= number_of_moves {
-                self.undo.actions = moves.to_owned();
+                self.undo.actions.truncate(self.undo.actions_performed);
+                self.undo.actions.extend_from_slice(&moves[i..]);
                 break;
             }
-            self.try_move(move_.direction)?;
+            // Perform the move as recorded rather than stepping in its direction, so pulls are
+            // replayed as pulls.
+            if let Ok(events) = self.perform_move(move_, true) {
+                for event in events {
+                    self.notify(&event);
+                }
+            } else {
+                // Take back what has been done so far so listeners see the level return to its
+                // previous state, then restore that state exactly.
+                for _ in 0..i {
+                    self.undo();
+                }
+                *self = backup;
+                return Err(InvalidMoves::IllegalMove {
+                    index: i,
+                    chr: move_.to_char(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Perform the given moves on a copy of the level to make sure all of them are possible and
+    /// push or pull crates exactly where they claim to.
+    fn validate_moves(&self, moves: &[Move]) -> Result<(), InvalidMoves> {
+        let mut dry_run = self.clone();
+        dry_run.listeners.clear();
+
+        for (index, r#move) in moves.iter().enumerate() {
+            let is_valid = match dry_run.evaluate_move(r#move.worker, r#move) {
+                Ok(verified) => {
+                    verified.crate_move.is_some() == (r#move.moves_crate || r#move.pulls_crate)
+                }
+                Err(_) => false,
+            };
+            if !is_valid || dry_run.perform_move(r#move, false).is_err() {
+                return Err(InvalidMoves::IllegalMove {
+                    index,
+                    chr: r#move.to_char(),
+                });
+            }
         }
 
         Ok(())
@@ -690,14 +1655,116 @@ impl CurrentLevel {
     /// Used for loading a level.
     pub fn all_moves_to_string(&self) -> String {
         // DEBT Should be part of load (?)
-        let mut result = String::with_capacity(self.undo.actions.len());
+        let mut result = self.forgotten_moves.clone();
+        result.reserve(self.undo.actions.len());
         for mv in &self.undo.actions {
             result.push(mv.to_char());
         }
         result
     }
+
+    /// Replay the given moves on a fresh copy of `level`, yielding the state of the board after
+    /// each move. The iterator ends early at the first move that is invalid or cannot be
+    /// performed.
+    pub fn replay_iter(level: &Level, moves: &str) -> impl Iterator<Item = String> {
+        let mut current_level: CurrentLevel = level.into();
+        let moves: Vec<_> = moves
+            .chars()
+            .map_while(|c| Move::try_from(c).ok())
+            .collect();
+
+        moves.into_iter().map_while(move |r#move| {
+            current_level.perform_move(&r#move, true).ok()?;
+            Some(current_level.to_string())
+        })
+    }
+
+    /// What is on top of the background at the given position? The first worker is always shown
+    /// as such, no matter which one is active, so the output can be parsed again.
+    fn foreground(&self, pos: Position) -> Foreground {
+        if self.dynamic.workers[0] == pos {
+            Foreground::Worker
+        } else if self.dynamic.workers[1..].contains(&pos) {
+            Foreground::OtherWorker
+        } else if self.is_crate(pos) {
+            Foreground::Crate
+        } else {
+            Foreground::None
+        }
+    }
+
+    /// Draw the level using Unicode characters, connecting adjacent walls using box-drawing
+    /// characters.
+    pub fn to_unicode_string(&self) -> String {
+        let is_wall = |pos: Position| self.in_bounds(pos) && self.background(pos).is_wall();
+
+        let mut result = String::with_capacity(4 * (self.columns + 1) * self.rows);
+        for i in 0..self.rows() {
+            if i != 0 {
+                result.push('\n');
+            }
+            for j in 0..self.columns() {
+                let pos = Position::new(j, i);
+                let background = self.background(pos);
+                let cell = if background.is_wall() {
+                    let [up, down, left, right] = [
+                        Direction::Up,
+                        Direction::Down,
+                        Direction::Left,
+                        Direction::Right,
+                    ]
+                    .map(|dir| is_wall(pos.neighbour(dir)));
+                    wall_to_unicode(up, down, left, right)
+                } else {
+                    cell_to_unicode(background, self.foreground(pos))
+                };
+                result.push(cell);
+            }
+        }
+        result
+    }
+}
+
+/// Pick the box-drawing character connecting a wall to those of its neighbours that are walls,
+/// too.
+fn wall_to_unicode(up: bool, down: bool, left: bool, right: bool) -> char {
+    match (up, down, left, right) {
+        (false, false, false, false) => '■',
+        (false, false, _, _) => '─',
+        (_, _, false, false) => '│',
+        (false, true, false, true) => '┌',
+        (false, true, true, false) => '┐',
+        (true, false, false, true) => '└',
+        (true, false, true, false) => '┘',
+        (true, true, false, true) => '├',
+        (true, true, true, false) => '┤',
+        (false, true, true, true) => '┬',
+        (true, false, true, true) => '┴',
+        (true, true, true, true) => '┼',
+    }
+}
+
+/// Like `cell_to_char`, but using Unicode symbols instead of the usual ASCII characters.
+fn cell_to_unicode(background: Background, foreground: Foreground) -> char {
+    match (background, foreground) {
+        (_, Foreground::Worker) | (_, Foreground::OtherWorker) => {
+            if background == Background::Goal {
+                '☻'
+            } else {
+                '☺'
+            }
+        }
+        (Background::Goal, Foreground::Crate) => '▣',
+        (_, Foreground::Crate) => '□',
+        (Background::Goal, Foreground::None) => '·',
+        (Background::Ice, Foreground::None) => '░',
+        _ => ' ',
+    }
 }
 
+/// The character representing a cell in the usual ASCII format. There are no characters for
+/// crates or workers on ice, so they are written as if they were on floor and the ice beneath
+/// them is lost when the output is parsed again.
 fn cell_to_char(background: Background, foreground: Foreground) -> char {
     match (background, foreground) {
         (Background::Wall, Foreground::None) => '#',
@@ -707,6 +1774,13 @@ fn cell_to_char(background: Background, foreground: Foreground) -> char {
         (Background::Goal, Foreground::None) => '.',
         (Background::Goal, Foreground::Crate) => '*',
         (Background::Goal, Foreground::Worker) => '+',
+        (Background::Floor, Foreground::OtherWorker) => '&',
+        (Background::Goal, Foreground::OtherWorker) => '%',
+        (Background::Ice, Foreground::None) => '~',
+        // There are no separate characters for crates or workers on ice.
+        (Background::Ice, Foreground::Crate) => '$',
+        (Background::Ice, Foreground::Worker) => '@',
+        (Background::Ice, Foreground::OtherWorker) => '&',
         _ => panic!(
             "Invalid combination: {:?} on top of {:?}",
             foreground, background
@@ -723,15 +1797,7 @@ impl fmt::Display for CurrentLevel {
             }
             for j in 0..columns {
                 let pos = Position::new(j, i);
-                let background = self.background(pos);
-                let foreground = if self.dynamic.worker_position == pos {
-                    Foreground::Worker
-                } else if self.is_crate(pos) {
-                    Foreground::Crate
-                } else {
-                    Foreground::None
-                };
-                let cell = cell_to_char(background, foreground);
+                let cell = cell_to_char(self.background(pos), self.foreground(pos));
                 write!(f, "{}", cell)?;
             }
         }
@@ -743,9 +1809,12 @@ impl From<&Level> for CurrentLevel {
     fn from(level: &Level) -> Self {
         let dynamic = DynamicEntities {
             crates: level.crates.clone(),
-            worker_position: level.worker_position,
-
-            empty_goals: 0,
+            workers: Some(level.worker_position)
+                .into_iter()
+                .chain(level.other_workers.iter().cloned())
+                .collect(),
+            active_worker: 0,
+            empty_goals: level.empty_goals,
         };
 
         let mut result = Self {
@@ -753,19 +1822,24 @@ impl From<&Level> for CurrentLevel {
             rows: level.rows,
             background: BackgroundEntities::new(level),
             dynamic,
+            worker_direction: Direction::Left,
 
             undo: Undo::new(),
+            forgotten_moves: String::new(),
+            teleported: false,
+            undos: 0,
+            commands: 0,
+
+            active_time: Duration::default(),
+            timer_started: Some(Instant::now()),
+            cancel: CancelHandle::default(),
+            explored: HashSet::new(),
 
             listeners: vec![],
+            batch_moves: false,
+            batch: RefCell::new(None),
         };
-
-        result.dynamic.empty_goals = result
-            .dynamic
-            .crates
-            .keys()
-            .filter(|&&pos| result.background(pos) != Background::Goal)
-            .count();
-
+        result.reveal_around(level.worker_position);
         result
     }
 }
@@ -792,8 +1866,8 @@ mod test {
         )
         .unwrap()
         .into();
-        assert_eq!(lvl.dynamic.worker_position.x, 1);
-        assert_eq!(lvl.dynamic.worker_position.y, 1);
+        assert_eq!(lvl.dynamic.worker_position().x, 1);
+        assert_eq!(lvl.dynamic.worker_position().y, 1);
 
         assert!(lvl.is_empty(Position::new(2, 1)));
         assert!(!lvl.is_empty(Position::new(0, 1)));
@@ -839,8 +1913,8 @@ mod test {
         )
         .unwrap()
         .into();
-        assert_eq!(lvl.dynamic.worker_position.x, 1);
-        assert_eq!(lvl.dynamic.worker_position.y, 3);
+        assert_eq!(lvl.dynamic.worker_position().x, 1);
+        assert_eq!(lvl.dynamic.worker_position().y, 3);
         assert!(!&lvl.try_move(Up).is_err());
         assert!(lvl.is_finished());
         assert!(&lvl.try_move(Up).is_err());
@@ -857,8 +1931,8 @@ mod test {
         )
         .unwrap()
         .into();
-        assert_eq!(lvl.dynamic.worker_position.x, 3);
-        assert_eq!(lvl.dynamic.worker_position.y, 1);
+        assert_eq!(lvl.dynamic.worker_position().x, 3);
+        assert_eq!(lvl.dynamic.worker_position().y, 1);
         assert_eq!(lvl.worker_direction(), Left);
         assert!(!&lvl.try_move(Right).is_err());
         assert!(!&lvl.try_move(Left).is_err());
@@ -877,6 +1951,508 @@ mod test {
         assert_eq!(lvl.worker_direction(), Left);
     }
 
+    #[test]
+    fn worker_direction_after_undo() {
+        use self::Direction::*;
+        use std::sync::mpsc::channel;
+
+        let mut lvl: CurrentLevel = Level::parse(
+            0,
+            "#######\n\
+             #.$@ .#\n\
+             #   $ #\n\
+             #######\n",
+        )
+        .unwrap()
+        .into();
+        let (sender, receiver) = channel();
+        lvl.subscribe(sender);
+
+        assert!(lvl.try_move(Right).is_ok());
+        assert_eq!(lvl.worker_direction(), Right);
+        receiver.try_iter().for_each(drop);
+
+        assert!(lvl.undo());
+        assert_eq!(lvl.worker_position(), Position::new(3, 1));
+        assert_eq!(lvl.worker_direction(), Left);
+        match receiver.try_recv() {
+            Ok(Event::MoveWorker { direction, .. }) => assert_eq!(direction, Left),
+            _ => unreachable!(),
+        }
+
+        assert!(lvl.redo());
+        assert_eq!(lvl.worker_direction(), Right);
+    }
+
+    #[test]
+    fn push_crate_to_nearest_goal() {
+        let mut lvl: CurrentLevel = Level::parse(
+            0,
+            "##########\n\
+             #. $  . $#\n\
+             #  @     #\n\
+             ##########\n",
+        )
+        .unwrap()
+        .into();
+
+        assert!(lvl
+            .push_crate_to_nearest_goal(Position::new(3, 1))
+            .is_some());
+        assert!(lvl.is_crate(Position::new(1, 1)));
+        assert!(!lvl.is_crate(Position::new(6, 1)));
+        assert_eq!(lvl.number_of_pushes(), 2);
+    }
+
+    #[test]
+    fn assign_crate_to_goal() {
+        let mut lvl: CurrentLevel = Level::parse(
+            0,
+            "##########\n\
+             #. $  . $#\n\
+             #  @     #\n\
+             ##########\n",
+        )
+        .unwrap()
+        .into();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        lvl.subscribe(sender);
+
+        // The crate at (8,1) cannot be moved away from the wall at all
+        assert!(lvl
+            .assign_crate_to_goal(Position::new(8, 1), Position::new(6, 1))
+            .is_none());
+        assert!(receiver.try_iter().any(|e| matches!(e, Event::NoPathFound)));
+
+        // Not a goal
+        assert!(lvl
+            .assign_crate_to_goal(Position::new(3, 1), Position::new(5, 1))
+            .is_none());
+        assert!(lvl.is_crate(Position::new(3, 1)));
+
+        // The goal at (1,1) is closer, but the crate is assigned to the one at (6,1)
+        assert!(lvl
+            .assign_crate_to_goal(Position::new(3, 1), Position::new(6, 1))
+            .is_some());
+        assert!(lvl.is_crate(Position::new(6, 1)));
+        assert!(!lvl.is_crate(Position::new(1, 1)));
+        assert_eq!(lvl.number_of_pushes(), 3);
+    }
+
+    #[test]
+    fn replay() {
+        let level = Level::parse(
+            0,
+            "#####\n\
+             #@  #\n\
+             # $.#\n\
+             #####",
+        )
+        .unwrap();
+
+        let frames: Vec<_> = CurrentLevel::replay_iter(&level, "dR").collect();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0], "#####\n#   #\n#@$.#\n#####");
+        assert_eq!(frames[1], "#####\n#   #\n# @*#\n#####");
+
+        // Stop at the first move that cannot be performed
+        assert_eq!(CurrentLevel::replay_iter(&level, "ldR").count(), 0);
+        assert_eq!(CurrentLevel::replay_iter(&level, "dRRd").count(), 2);
+    }
+
+    #[test]
+    fn seek() {
+        let level = Level::parse(0, "#########\n#@    $.#\n#########").unwrap();
+        let mut lvl: CurrentLevel = level.clone().into();
+        lvl.execute_moves(5, "rrrrR").unwrap();
+        assert!(lvl.is_finished());
+
+        lvl.seek(2);
+        assert_eq!(lvl.number_of_moves(), 2);
+        assert_eq!(lvl.to_string(), "#########\n#  @  $.#\n#########");
+
+        lvl.seek(5);
+        assert!(lvl.is_finished());
+
+        lvl.seek(100);
+        assert_eq!(lvl.number_of_moves(), 5);
+        lvl.seek(0);
+        assert_eq!(lvl.to_string(), level.to_string());
+    }
+
+    #[test]
+    fn snapshot_round_trip() {
+        use self::Direction::*;
+
+        let mut lvl: CurrentLevel = Level::parse(
+            0,
+            "#######\n\
+             #.$@$.#\n\
+             #######\n",
+        )
+        .unwrap()
+        .into();
+        assert!(lvl.try_move(Right).is_ok());
+        let snapshot = lvl.snapshot();
+        let bytes = serde_cbor::to_vec(&snapshot).unwrap();
+        let snapshot: LevelSnapshot = serde_cbor::from_slice(&bytes).unwrap();
+
+        let mut other: CurrentLevel = Level::parse(0, "####\n#@*#\n####").unwrap().into();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        other.subscribe(sender);
+        other.restore(snapshot.clone(), 1);
+
+        assert_eq!(other.snapshot(), snapshot);
+        assert_eq!(other.to_string(), lvl.to_string());
+        assert_eq!(other.number_of_moves(), 0);
+        assert!(!other.is_finished());
+        assert!(other.try_move(Left).is_ok());
+        assert!(other.try_move(Left).is_ok());
+        assert!(other.is_finished());
+        match receiver.try_recv() {
+            Ok(Event::InitialLevelState { columns, rows, .. }) => {
+                assert_eq!((columns, rows), (7, 3))
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn teleport_worker() {
+        use std::sync::mpsc::channel;
+
+        let mut lvl: CurrentLevel = Level::parse(
+            0,
+            "#######\n\
+             #@ #. #\n\
+             #  #$ #\n\
+             #######",
+        )
+        .unwrap()
+        .into();
+        let (sender, receiver) = channel();
+        lvl.subscribe(sender);
+        lvl.step(Direction::Down);
+
+        // The target cannot be reached by walking
+        assert!(lvl.teleport_worker(Position::new(5, 1)));
+        assert_eq!(lvl.worker_position(), Position::new(5, 1));
+        assert!(lvl.is_pristine());
+        match receiver.try_iter().last() {
+            Some(Event::MoveWorker { to, .. }) => assert_eq!(to, Position::new(5, 1)),
+            e => panic!("unexpected event: {:?}", e),
+        }
+
+        assert!(!lvl.teleport_worker(Position::new(3, 1)));
+        assert!(!lvl.teleport_worker(Position::new(4, 2)));
+        assert!(!lvl.teleport_worker(Position::new(10, 10)));
+        assert_eq!(lvl.worker_position(), Position::new(5, 1));
+        assert!(receiver.try_iter().all(|e| e.is_error()));
+    }
+
+    #[test]
+    fn two_workers() {
+        use self::Direction::*;
+
+        let level = Level::parse(0, "#########\n#@ $. & #\n#########").unwrap();
+        assert_eq!(level.other_workers, vec![Position::new(6, 1)]);
+        let mut lvl: CurrentLevel = level.into();
+
+        assert!(lvl.try_move(Right).is_ok());
+        assert!(lvl.select_worker(1));
+        assert!(!lvl.select_worker(2));
+        assert!(lvl.try_move(Right).is_ok());
+        assert_eq!(lvl.worker_position(), Position::new(7, 1));
+        assert_eq!(lvl.to_string(), "#########\n# @$.  &#\n#########");
+
+        for _ in 0..3 {
+            assert!(lvl.try_move(Left).is_ok());
+        }
+        // The crate cannot be pushed onto the other worker.
+        assert_eq!(
+            lvl.try_move(Left).unwrap_err().obstacle_type,
+            Obstacle::Worker
+        );
+
+        assert!(lvl.select_worker(0));
+        assert_eq!(lvl.worker_position(), Position::new(2, 1));
+        assert!(lvl.try_move(Left).is_ok());
+        assert_eq!(lvl.worker_position(), Position::new(1, 1));
+    }
+
+    #[test]
+    fn undo_with_two_workers() {
+        use self::Direction::*;
+        use std::sync::mpsc::channel;
+
+        let s = "#########\n#@ $. & #\n#########";
+        let mut lvl: CurrentLevel = Level::parse(0, s).unwrap().into();
+        let (sender, receiver) = channel();
+        lvl.subscribe(sender);
+
+        assert!(lvl.try_move(Right).is_ok());
+        assert!(lvl.select_worker(1));
+        assert!(lvl.try_move(Left).is_ok());
+        let worker_ids: Vec<_> = receiver
+            .try_iter()
+            .filter_map(|event| match event {
+                Event::MoveWorker { worker_id, .. } => Some(worker_id),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(worker_ids, vec![0, 1]);
+
+        // Each undo moves the worker that made the move, no matter which one is active.
+        assert!(lvl.select_worker(0));
+        assert!(lvl.undo());
+        assert_eq!(lvl.to_string(), "#########\n# @$. & #\n#########");
+        assert!(lvl.undo());
+        assert_eq!(lvl.to_string(), s);
+        match receiver.try_iter().last() {
+            Some(Event::MoveWorker { worker_id: 0, .. }) => {}
+            e => panic!("unexpected event: {:?}", e),
+        }
+
+        assert!(lvl.redo());
+        assert!(lvl.redo());
+        assert_eq!(lvl.to_string(), "#########\n# @$.&  #\n#########");
+        assert_eq!(lvl.worker_position(), Position::new(2, 1));
+    }
+
+    #[test]
+    fn other_worker_on_goal() {
+        use self::Direction::*;
+
+        let s = "#########\n#@$.% $ #\n#########";
+        let level = Level::parse(0, s).unwrap();
+        assert_eq!(level.other_workers, vec![Position::new(4, 1)]);
+        assert_eq!(level.number_of_goals(), 2);
+        assert_eq!(level.to_string(), s);
+
+        let mut lvl: CurrentLevel = level.into();
+        assert!(lvl.select_worker(1));
+        assert!(lvl.try_move(Right).is_ok());
+        assert_eq!(lvl.to_string(), "#########\n#@$..&$ #\n#########");
+        assert!(lvl.try_move(Left).is_ok());
+        assert_eq!(lvl.to_string(), s);
+    }
+
+    #[test]
+    fn unicode_walls() {
+        let lvl: CurrentLevel = Level::parse(
+            0,
+            "######\n\
+             #@$. #\n\
+             ## * #\n\
+             ######",
+        )
+        .unwrap()
+        .into();
+        assert_eq!(
+            lvl.to_unicode_string(),
+            "┌────┐\n\
+             │☺□· │\n\
+             ├┐ ▣ │\n\
+             └┴───┘"
+        );
+    }
+
+    #[test]
+    fn pause_timer() {
+        let mut lvl: CurrentLevel = Level::parse(0, "#####\n#@$.#\n#####").unwrap().into();
+        let start = Instant::now();
+        let seconds = Duration::from_secs;
+
+        lvl.pause_timer_at(start);
+        let active = lvl.active_time_at(start);
+        lvl.resume_timer_at(start + seconds(10));
+        lvl.pause_timer_at(start + seconds(13));
+        assert_eq!(lvl.active_time_at(start + seconds(20)), active + seconds(3));
+
+        // Pausing or resuming twice has no effect
+        lvl.pause_timer_at(start + seconds(20));
+        lvl.resume_timer_at(start + seconds(30));
+        lvl.resume_timer_at(start + seconds(31));
+        assert_eq!(lvl.active_time_at(start + seconds(32)), active + seconds(5));
+    }
+
+    #[test]
+    fn corrupted_moves() {
+        let level = Level::parse(0, "#####\n#@  #\n# $.#\n#####").unwrap();
+
+        let mut lvl: CurrentLevel = level.clone().into();
+        // The second move is not a push, and the last one is blocked by a wall.
+        assert_eq!(
+            lvl.execute_moves(1, "drR"),
+            Err(InvalidMoves::IllegalMove { index: 1, chr: 'r' })
+        );
+        assert_eq!(
+            lvl.execute_moves(1, "dRR"),
+            Err(InvalidMoves::IllegalMove { index: 2, chr: 'R' })
+        );
+        assert_eq!(
+            lvl.execute_moves(0, "dx"),
+            Err(InvalidMoves::InvalidCharacter('x'))
+        );
+        assert!(lvl.is_pristine());
+        assert_eq!(lvl.to_string(), level.to_string());
+
+        assert!(lvl.execute_moves(1, "dR").is_ok());
+        assert_eq!(lvl.number_of_moves(), 1);
+    }
+
+    #[test]
+    fn stable_crate_ids() {
+        let level = Level::parse(
+            0,
+            "######\n\
+             #@$ .#\n\
+             # $ .#\n\
+             ######",
+        )
+        .unwrap();
+        let mut lvl: CurrentLevel = level.clone().into();
+        assert_eq!(lvl.crate_id_at(Position::new(2, 1)), Some(0));
+        assert_eq!(lvl.crate_id_at(Position::new(2, 2)), Some(1));
+        assert_eq!(lvl.crate_id_at(Position::new(1, 1)), None);
+
+        assert!(lvl.try_move(Direction::Right).is_ok());
+        assert_eq!(lvl.crate_id_at(Position::new(3, 1)), Some(0));
+        assert_eq!(lvl.crate_position(0), Some(Position::new(3, 1)));
+        assert_eq!(lvl.crate_position(1), Some(Position::new(2, 2)));
+        assert_eq!(lvl.crate_position(2), None);
+
+        // Starting over assigns the same ids again.
+        let lvl: CurrentLevel = level.into();
+        assert_eq!(lvl.crate_position(0), Some(Position::new(2, 1)));
+    }
+
+    #[test]
+    fn explore_room() {
+        use std::sync::mpsc::channel;
+
+        let mut lvl: CurrentLevel = Level::parse(
+            0,
+            "#######\n\
+             #@    #\n\
+             #    $#\n\
+             #    .#\n\
+             #######",
+        )
+        .unwrap()
+        .into();
+        let (sender, receiver) = channel();
+        lvl.subscribe(sender);
+        assert_eq!(lvl.explored_cells().len(), 5);
+
+        let mut explored = lvl.explored_cells().clone();
+        for &direction in &[
+            Direction::Right,
+            Direction::Right,
+            Direction::Down,
+            Direction::Up,
+        ] {
+            lvl.step(direction);
+            assert!(lvl.explored_cells().is_superset(&explored));
+            explored = lvl.explored_cells().clone();
+        }
+        assert!(explored.contains(&Position::new(4, 2)));
+        assert!(!explored.contains(&Position::new(5, 3)));
+
+        let revealed: Vec<_> = receiver
+            .try_iter()
+            .filter_map(|e| match e {
+                Event::CellsRevealed { positions } => Some(positions),
+                _ => None,
+            })
+            .collect();
+        // Walking back up does not reveal anything new.
+        assert_eq!(revealed.len(), 3);
+        assert_eq!(
+            revealed[0],
+            vec![
+                Position::new(2, 0),
+                Position::new(3, 1),
+                Position::new(2, 2)
+            ]
+        );
+    }
+
+    #[test]
+    fn undo_to_last_push() {
+        let mut lvl: CurrentLevel = Level::parse(
+            0,
+            "########\n\
+             #@$   .#\n\
+             #      #\n\
+             ########",
+        )
+        .unwrap()
+        .into();
+        lvl.execute_moves(6, "RduRdl").unwrap();
+
+        lvl.undo_to_last_push();
+        assert_eq!(lvl.moves_to_string(), "Rdu");
+        assert_eq!(lvl.worker_position(), Position::new(2, 1));
+        assert_eq!(lvl.crate_position(0), Some(Position::new(3, 1)));
+
+        lvl.undo_to_last_push();
+        assert!(lvl.is_pristine());
+        lvl.undo_to_last_push();
+        assert!(lvl.is_pristine());
+    }
+
+    #[test]
+    fn ice_under_crates_and_workers_not_printed() {
+        let s = "########\n\
+                 #@$~~#.#\n\
+                 #      #\n\
+                 ########";
+        let mut lvl: CurrentLevel = Level::parse(0, s).unwrap().into();
+        lvl.try_move(Direction::Right).unwrap();
+        assert!(lvl.teleport_worker(Position::new(3, 1)));
+        let printed = lvl.to_string();
+        assert_eq!(printed, "########\n#  @$#.#\n#      #\n########");
+
+        let reparsed: CurrentLevel = Level::parse(0, &printed).unwrap().into();
+        assert_eq!(reparsed.background(Position::new(3, 1)), Background::Floor);
+        assert_eq!(reparsed.background(Position::new(4, 1)), Background::Floor);
+    }
+
+    #[test]
+    fn crate_slides_on_ice() {
+        use self::Direction::*;
+
+        let mut lvl: CurrentLevel = Level::parse(
+            0,
+            "#########\n\
+             #@$~~~ .#\n\
+             #########\n",
+        )
+        .unwrap()
+        .into();
+
+        assert!(lvl.try_move(Right).is_ok());
+        assert_eq!(lvl.worker_position(), Position::new(2, 1));
+        assert_eq!(lvl.crate_positions(), vec![Position::new(5, 1)]);
+        assert_eq!(lvl.to_string(), "#########\n# @~~$ .#\n#########");
+
+        assert!(lvl.undo());
+        assert_eq!(lvl.worker_position(), Position::new(1, 1));
+        assert_eq!(lvl.crate_positions(), vec![Position::new(2, 1)]);
+
+        assert!(lvl.redo());
+        assert_eq!(lvl.crate_positions(), vec![Position::new(5, 1)]);
+
+        // Off the ice, the crate moves one cell at a time again.
+        assert!(lvl.try_move(Right).is_ok());
+        assert!(lvl.try_move(Right).is_ok());
+        assert!(lvl.try_move(Right).is_ok());
+        assert_eq!(lvl.crate_positions(), vec![Position::new(6, 1)]);
+        assert!(lvl.try_move(Right).is_ok());
+        assert!(lvl.is_finished());
+    }
+
     #[test]
     fn walk_to_obstacle_test() {
         let lvl: CurrentLevel = Level::parse(
@@ -900,6 +2476,59 @@ mod test {
         assert_eq!(go(Direction::Right).unwrap_or_default().len(), 1);
     }
 
+    #[test]
+    fn stop_at_open_side() {
+        // The level is not enclosed by walls on the right, so the cell right of the worker is
+        // the last one it may enter.
+        let lvl: CurrentLevel = Level::parse(
+            0,
+            "######\n\
+             #.$@ \n\
+             ######\n",
+        )
+        .unwrap()
+        .into();
+        assert_eq!(lvl.background(Position::new(5, 1)), Background::Empty);
+
+        let mut dynamic = lvl.dynamic.clone();
+        let moves = lvl
+            .walk_to_obstacle(Direction::Right, &mut dynamic)
+            .unwrap();
+        assert_eq!(moves.len(), 1);
+        assert_eq!(dynamic.worker_position(), Position::new(4, 1));
+
+        for &may_push_crate in &[false, true] {
+            let mut lvl = lvl.clone();
+            lvl.move_as_far_as_possible(Direction::Right, may_push_crate);
+            assert_eq!(lvl.worker_position(), Position::new(4, 1));
+        }
+    }
+
+    #[test]
+    fn obstacle_in_direction() {
+        let lvl: CurrentLevel = Level::parse(
+            0,
+            "########\n\
+             #@  $ .#\n\
+             ########\n",
+        )
+        .unwrap()
+        .into();
+
+        assert_eq!(
+            lvl.obstacle_in_direction(Direction::Right),
+            (2, Obstacle::Crate)
+        );
+        assert_eq!(
+            lvl.obstacle_in_direction(Direction::Left),
+            (0, Obstacle::Wall)
+        );
+        assert_eq!(
+            lvl.obstacle_in_direction(Direction::Down),
+            (0, Obstacle::Wall)
+        );
+    }
+
     #[test]
     fn push_to_obstacle_test() {
         let lvl: CurrentLevel = Level::parse(
@@ -922,4 +2551,439 @@ mod test {
         let mut go = |dir| lvl.push_to_obstacle(dir, &mut dynamic);
         assert_eq!(go(Direction::Right).unwrap_or_default().len(), 2);
     }
+
+    #[test]
+    fn frozen_crates() {
+        let s = "#######\n\
+                 # $$  #\n\
+                 #  @$ #\n\
+                 #...  #\n\
+                 #######";
+        let mut lvl: CurrentLevel = Level::parse(0, s).unwrap().into();
+        assert_eq!(
+            lvl.frozen_crates(),
+            vec![Position::new(2, 1), Position::new(3, 1)]
+        );
+
+        // A crate pushed against a single wall can still be moved along it
+        assert!(lvl.try_move(Direction::Right).is_ok());
+        assert_eq!(lvl.frozen_crates().len(), 2);
+
+        // but not once it is in a corner
+        for &dir in &[Direction::Up, Direction::Right, Direction::Down] {
+            assert!(lvl.try_move(dir).is_ok());
+        }
+        assert_eq!(lvl.frozen_crates().len(), 3);
+    }
+
+    #[test]
+    fn box_lines() {
+        use Direction::*;
+        let s = "#######\n\
+                 #@$  .#\n\
+                 # $  .#\n\
+                 #######";
+        let mut lvl: CurrentLevel = Level::parse(0, s).unwrap().into();
+        let mut go = |dirs: &[Direction]| {
+            for &dir in dirs {
+                assert!(lvl.try_move(dir).is_ok());
+            }
+            (lvl.number_of_pushes(), lvl.number_of_box_lines())
+        };
+
+        assert_eq!(go(&[Right, Right, Right]), (3, 1));
+        assert_eq!(go(&[Left, Left, Left, Down]), (3, 1));
+        assert_eq!(go(&[Right, Right, Right]), (6, 2));
+    }
+
+    #[test]
+    fn box_lines_with_two_workers() {
+        use Direction::*;
+        let s = "#######\n\
+                 #@$  .#\n\
+                 #&    #\n\
+                 #######";
+        let mut lvl: CurrentLevel = Level::parse(0, s).unwrap().into();
+
+        assert!(lvl.try_move(Right).is_ok());
+        assert!(lvl.select_worker(1));
+        assert!(lvl.try_move(Right).is_ok());
+        assert!(lvl.select_worker(0));
+        assert!(lvl.try_move(Right).is_ok());
+
+        // The other worker walking around does not interrupt the box line.
+        assert_eq!(lvl.number_of_pushes(), 2);
+        assert_eq!(lvl.number_of_box_lines(), 1);
+    }
+
+    #[test]
+    fn final_facing() {
+        let s = "######\n\
+                 #@   #\n\
+                 #  $.#\n\
+                 ######";
+        let mut lvl: CurrentLevel = Level::parse(0, s).unwrap().into();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        lvl.subscribe(sender);
+
+        let target = Position::new(3, 1);
+        assert!(lvl.move_to(target, false, Some(Direction::Down)).is_some());
+        assert_eq!(lvl.worker_position(), target);
+        assert_eq!(lvl.worker_direction(), Direction::Down);
+        assert_eq!(lvl.number_of_moves(), 2);
+
+        let last = receiver.try_iter().last();
+        match last {
+            Some(Event::MoveWorker {
+                worker_id: 0,
+                from,
+                to,
+                direction,
+            }) => {
+                assert_eq!(from, target);
+                assert_eq!(to, target);
+                assert_eq!(direction, Direction::Down);
+            }
+            _ => panic!("Expected the worker to turn, got {:?}", last),
+        }
+    }
+
+    #[test]
+    fn crate_ids_after_loading() {
+        let s = "#######\n\
+                 #@$ . #\n\
+                 #  $. #\n\
+                 #     #\n\
+                 #######";
+        let level = Level::parse(0, s).unwrap();
+        let moves = "RRldR";
+        let performed = 3;
+
+        let mut loaded: CurrentLevel = level.clone().into();
+        loaded.execute_moves(performed, moves).unwrap();
+
+        let mut fresh: CurrentLevel = level.into();
+        for mv in crate::move_::parse(&moves[..performed]).unwrap() {
+            assert!(fresh.try_move(mv.direction).is_ok());
+        }
+
+        assert_eq!(loaded.to_string(), fresh.to_string());
+        for id in 0..2 {
+            assert_eq!(loaded.crate_position(id), fresh.crate_position(id));
+        }
+        assert_eq!(loaded.crate_id_at(Position::new(3, 2)), Some(1));
+
+        // Redoing the remaining moves yields the same ids as playing them
+        while loaded.redo() {}
+        for mv in crate::move_::parse(&moves[performed..]).unwrap() {
+            assert!(fresh.try_move(mv.direction).is_ok());
+        }
+        assert!(loaded.is_finished());
+        for id in 0..2 {
+            assert_eq!(loaded.crate_position(id), fresh.crate_position(id));
+        }
+    }
+
+    #[test]
+    fn pull_crate() {
+        let s = "#######\n\
+                 #.$@  #\n\
+                 #######";
+        let mut lvl: CurrentLevel = Level::parse(0, s).unwrap().into();
+
+        lvl.pull(Direction::Right).unwrap();
+        assert_eq!(lvl.worker_position(), Position::new(4, 1));
+        assert_eq!(lvl.crate_id_at(Position::new(3, 1)), Some(0));
+        assert_eq!(lvl.number_of_pushes(), 0);
+        assert_eq!(lvl.moves_to_string(), "→");
+
+        // Without a crate behind the worker, pulling is just walking
+        lvl.step(Direction::Right);
+        lvl.pull(Direction::Left).unwrap();
+        assert_eq!(lvl.crate_id_at(Position::new(3, 1)), Some(0));
+        assert_eq!(lvl.moves_to_string(), "→rl");
+
+        assert!(lvl.undo());
+        assert!(lvl.undo());
+        assert!(lvl.undo());
+        assert_eq!(lvl.worker_position(), Position::new(3, 1));
+        assert_eq!(lvl.crate_id_at(Position::new(2, 1)), Some(0));
+        assert!(lvl.redo());
+        assert_eq!(lvl.crate_id_at(Position::new(3, 1)), Some(0));
+    }
+
+    #[test]
+    fn reload_pulls() {
+        let s = "#######\n\
+                 #.$@  #\n\
+                 #######";
+        let level = Level::parse(0, s).unwrap();
+        let mut lvl: CurrentLevel = level.clone().into();
+        lvl.pull(Direction::Right).unwrap();
+        lvl.step(Direction::Right);
+        lvl.pull(Direction::Left).unwrap();
+        lvl.undo();
+
+        let mut reloaded: CurrentLevel = level.into();
+        reloaded
+            .execute_moves(lvl.number_of_moves(), &lvl.all_moves_to_string())
+            .unwrap();
+        assert!(reloaded.has_pulls());
+        assert_eq!(reloaded.worker_position(), lvl.worker_position());
+        assert_eq!(reloaded.crate_positions(), lvl.crate_positions());
+        assert_eq!(reloaded.all_moves_to_string(), "→rl");
+        assert!(reloaded.redo());
+        assert_eq!(reloaded.worker_position(), Position::new(4, 1));
+    }
+
+    #[test]
+    fn checkpoint() {
+        let s = "########\n\
+                 #@  $ .#\n\
+                 #      #\n\
+                 ########";
+        let mut lvl: CurrentLevel = Level::parse(0, s).unwrap().into();
+        assert!(!lvl.restore_checkpoint());
+
+        for &dir in &[Direction::Right, Direction::Down, Direction::Right] {
+            lvl.try_move(dir).unwrap();
+        }
+        lvl.set_checkpoint();
+        let worker_position = lvl.worker_position();
+        for &dir in &[Direction::Up, Direction::Right] {
+            lvl.try_move(dir).unwrap();
+        }
+        assert_eq!(lvl.number_of_pushes(), 1);
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        lvl.subscribe(sender);
+        assert!(lvl.restore_checkpoint());
+        assert_eq!(lvl.number_of_moves(), 3);
+        assert_eq!(lvl.number_of_pushes(), 0);
+        assert_eq!(lvl.worker_position(), worker_position);
+        let moves = receiver
+            .try_iter()
+            .filter(|e| matches!(e, Event::MoveWorker { .. }))
+            .count();
+        assert_eq!(moves, 2);
+
+        // Going back past the checkpoint and returning to it redoes the moves
+        lvl.undo();
+        assert!(lvl.restore_checkpoint());
+        assert_eq!(lvl.number_of_moves(), 3);
+
+        // Taking a different path from before the checkpoint makes it unreachable
+        lvl.undo();
+        lvl.try_move(Direction::Up).unwrap();
+        assert!(!lvl.restore_checkpoint());
+    }
+
+    #[test]
+    fn limited_history() {
+        let level = Level::parse(0, "#######\n#@  $.#\n#######").unwrap();
+        let mut lvl: CurrentLevel = level.clone().into();
+        lvl.set_max_history(Some(2));
+        for _ in 0..3 {
+            lvl.try_move(Direction::Right).unwrap();
+        }
+        assert!(lvl.is_finished());
+        assert!(!lvl.has_complete_history());
+        assert_eq!(lvl.number_of_moves(), 3);
+        assert_eq!(lvl.number_of_pushes(), 1);
+        assert_eq!(lvl.moves_to_string(), "rrR");
+
+        // The forgotten move cannot be undone, but it still counts
+        assert!(lvl.undo());
+        assert!(lvl.undo());
+        assert!(!lvl.undo());
+        assert!(!lvl.is_pristine());
+        assert_eq!(lvl.number_of_moves(), 1);
+
+        // Saved moves replay from the initial state
+        let mut reloaded: CurrentLevel = level.into();
+        reloaded
+            .execute_moves(lvl.number_of_moves(), &lvl.all_moves_to_string())
+            .unwrap();
+        assert_eq!(reloaded.worker_position(), lvl.worker_position());
+        lvl.seek(3);
+        reloaded.seek(3);
+        assert!(reloaded.is_finished());
+        assert_eq!(reloaded.moves_to_string(), "rrR");
+    }
+
+    #[test]
+    fn checkpoint_outside_limited_history() {
+        let s = "########\n\
+                 #@     #\n\
+                 #  $  .#\n\
+                 ########";
+        let mut lvl: CurrentLevel = Level::parse(0, s).unwrap().into();
+        lvl.set_max_history(Some(2));
+
+        lvl.try_move(Direction::Right).unwrap();
+        lvl.set_checkpoint();
+        lvl.try_move(Direction::Right).unwrap();
+        assert!(lvl.restore_checkpoint());
+        assert_eq!(lvl.worker_position(), Position::new(2, 1));
+
+        for _ in 0..2 {
+            lvl.try_move(Direction::Right).unwrap();
+        }
+        lvl.try_move(Direction::Down).unwrap();
+        assert!(!lvl.restore_checkpoint());
+        assert_eq!(lvl.worker_position(), Position::new(4, 2));
+    }
+
+    #[test]
+    fn undo_restores_empty_goals() {
+        let s = "######\n\
+                 #@*  #\n\
+                 ######";
+        let mut lvl: CurrentLevel = Level::parse(0, s).unwrap().into();
+        assert!(lvl.is_finished());
+
+        lvl.try_move(Direction::Right).unwrap();
+        assert_eq!(lvl.dynamic.empty_goals, 1);
+        assert!(!lvl.is_finished());
+
+        assert!(lvl.undo());
+        assert_eq!(lvl.dynamic.empty_goals, 0);
+        assert!(lvl.is_finished());
+        assert_eq!(lvl.worker_position(), Position::new(1, 1));
+        assert!(lvl.is_crate(Position::new(2, 1)));
+
+        // A redone move can be undone just the same.
+        assert!(lvl.redo());
+        assert_eq!(lvl.dynamic.empty_goals, 1);
+        assert!(lvl.undo());
+        assert_eq!(lvl.dynamic.empty_goals, 0);
+        assert!(lvl.is_crate(Position::new(2, 1)));
+    }
+
+    #[test]
+    fn background_unchanged_by_push() {
+        // The GUI renders the background once per level, so moving crates must not change it.
+        let s = "#######\n\
+                 #@$ . #\n\
+                 #######";
+        let mut lvl: CurrentLevel = Level::parse(0, s).unwrap().into();
+        let background = lvl.background_cells();
+
+        lvl.try_move(Direction::Right).unwrap();
+        lvl.try_move(Direction::Right).unwrap();
+        assert!(lvl.is_finished());
+        assert_eq!(lvl.background_cells(), background);
+
+        lvl.undo();
+        assert_eq!(lvl.background_cells(), background);
+    }
+
+    #[test]
+    fn crate_on_goal() {
+        let s = "########\n\
+                 #@$.$ .#\n\
+                 ########";
+        let mut lvl: CurrentLevel = Level::parse(0, s).unwrap().into();
+        assert_eq!(lvl.crate_on_goal(0), Some(false));
+        assert_eq!(lvl.crate_on_goal(1), Some(false));
+
+        lvl.try_move(Direction::Right).unwrap();
+        assert_eq!(lvl.crate_on_goal(0), Some(true));
+        assert_eq!(lvl.crate_on_goal(1), Some(false));
+        assert_eq!(lvl.crate_on_goal(2), None);
+    }
+
+    #[test]
+    fn crate_events_before_worker_events() {
+        let s = "######\n\
+                 #@$ .#\n\
+                 ######";
+        let mut lvl: CurrentLevel = Level::parse(0, s).unwrap().into();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        lvl.subscribe(sender);
+        let event_order = || -> Vec<&str> {
+            receiver
+                .try_iter()
+                .filter_map(|event| match event {
+                    Event::MoveCrate { .. } => Some("crate"),
+                    Event::MoveWorker { .. } => Some("worker"),
+                    _ => None,
+                })
+                .collect()
+        };
+
+        lvl.step(Direction::Right);
+        assert_eq!(event_order(), vec!["crate", "worker"]);
+        assert!(lvl.undo());
+        assert_eq!(event_order(), vec!["crate", "worker"]);
+        assert!(lvl.redo());
+        assert_eq!(event_order(), vec!["crate", "worker"]);
+    }
+
+    #[test]
+    fn annotated_moves() {
+        let s = "#######\n\
+                 #@ $ .#\n\
+                 #######";
+        let mut lvl: CurrentLevel = Level::parse(0, s).unwrap().into();
+        for _ in 0..3 {
+            lvl.try_move(Direction::Right).unwrap();
+        }
+        lvl.try_move(Direction::Left).unwrap();
+        lvl.undo();
+
+        let moves = lvl.annotated_moves();
+        assert_eq!(moves.len(), 3);
+        assert_eq!(moves[0], (Move::new(Direction::Right, false), false, None));
+        assert_eq!(
+            moves[1],
+            (
+                Move::new(Direction::Right, true),
+                true,
+                Some(Position::new(4, 1))
+            )
+        );
+        assert_eq!(
+            moves[2],
+            (
+                Move::new(Direction::Right, true),
+                true,
+                Some(Position::new(5, 1))
+            )
+        );
+    }
+
+    #[test]
+    fn wall_edges() {
+        use Direction::*;
+        use EdgeKind::*;
+
+        let s = "#####\n\
+                 #@* #\n\
+                 #####";
+        let lvl: CurrentLevel = Level::parse(0, s).unwrap().into();
+        let expected = vec![
+            (Position::new(1, 0), Down, WallToFloor),
+            (Position::new(2, 0), Down, WallToFloor),
+            (Position::new(3, 0), Down, WallToFloor),
+            (Position::new(0, 1), Right, WallToFloor),
+            (Position::new(4, 1), Left, WallToFloor),
+            (Position::new(1, 2), Up, WallToFloor),
+            (Position::new(2, 2), Up, WallToFloor),
+            (Position::new(3, 2), Up, WallToFloor),
+        ];
+        assert_eq!(lvl.wall_edges(), expected);
+
+        let s = " ###\n\
+                 ##@#\n\
+                 #*##\n\
+                 ###";
+        let lvl: CurrentLevel = Level::parse(0, s).unwrap().into();
+        let edges = lvl.wall_edges();
+        assert!(edges.contains(&(Position::new(1, 0), Left, WallToEmpty)));
+        assert!(edges.contains(&(Position::new(0, 1), Up, WallToEmpty)));
+        assert!(edges.contains(&(Position::new(1, 1), Right, WallToFloor)));
+        assert!(edges.contains(&(Position::new(2, 3), Right, WallToEmpty)));
+        assert_eq!(edges.iter().filter(|edge| edge.2 == WallToEmpty).count(), 4);
+    }
 }
@@ -64,10 +64,7 @@ impl Graph<Position> {
             if let DirectionResult::Neighbour { direction } =
                 direction(positions[len - i], positions[len - i - 1])
             {
-                steps.push(Move {
-                    direction,
-                    moves_crate: true,
-                });
+                steps.push(Move::new(direction, true));
             } else {
                 unreachable!();
             }
@@ -1,4 +1,5 @@
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
 
 use crate::current_level::graph::Graph;
 use crate::current_level::*;
@@ -6,6 +7,7 @@ use crate::direction::*;
 use crate::event::Event;
 use crate::move_::Move;
 use crate::position::*;
+use crate::util::flood_fill;
 
 pub struct Path {
     pub start: Position,
@@ -16,27 +18,113 @@ impl CurrentLevel {
     /// Try to find a shortest path from the workers current position to `to` and execute it if one
     /// exists. Otherwise, emit `Event::NoPathFound`.
     pub fn find_path(&mut self, to: Position) -> Option<Path> {
-        let columns = self.columns();
-        let rows = self.rows();
-
-        if self.dynamic.worker_position == to || !self.is_empty(to) {
+        if self.dynamic.worker_position() == to || !self.is_empty(to) {
             return Some(Path {
-                start: self.dynamic.worker_position,
+                start: self.dynamic.worker_position(),
                 steps: vec![],
             });
         }
 
-        let mut distances = vec![::std::usize::MAX; columns * rows];
+        let distances = if let Some(distances) = self.distances_to(to) {
+            distances
+        } else {
+            self.notify(&Event::NoPathFound);
+            return None;
+        };
+
+        let mut path = Path {
+            start: self.dynamic.worker_position(),
+            steps: vec![],
+        };
+
+        // Move worker along the path. Where there are several shortest paths, keep going in the
+        // same direction as long as possible to avoid zigzagging.
+        let mut pos = self.dynamic.worker_position();
+        let mut previous_direction = None;
+        while pos != to {
+            let dist = distances[self.index(pos)];
+            let (dir, next) = self
+                .empty_neighbours(pos)
+                .into_iter()
+                .filter(|&neighbour| distances[self.index(neighbour)] < dist)
+                .map(|neighbour| match direction(pos, neighbour) {
+                    DirectionResult::Neighbour { direction } => (direction, neighbour),
+                    _ => unreachable!(),
+                })
+                .min_by_key(|&(dir, _)| Some(dir) != previous_direction)
+                .unwrap();
+
+            path.steps.push(Move::new(dir, false));
+            previous_direction = Some(dir);
+            pos = next;
+        }
+
+        Some(path)
+    }
+
+    /// The number of steps the worker needs to walk to `to` without pushing any crates, or `None`
+    /// if there is no such path. Unlike `find_path`, this neither builds the path nor emits any
+    /// events.
+    pub fn path_length(&self, to: Position) -> Option<usize> {
+        if self.dynamic.worker_position() == to {
+            Some(0)
+        } else if !self.is_empty(to) {
+            None
+        } else {
+            let distances = self.distances_to(to)?;
+            Some(distances[self.index(self.dynamic.worker_position())])
+        }
+    }
+
+    /// All cells the worker can reach without pushing any crates, including its current
+    /// position, in the order of the underlying grid.
+    pub fn reachable_cells(&self) -> Vec<Position> {
+        let reachable = flood_fill(
+            &[self.dynamic.worker_position()],
+            self.columns(),
+            self.rows(),
+            |pos| self.is_empty(pos),
+        );
+
+        self.bounds()
+            .iter()
+            .filter(|pos| reachable.contains(pos))
+            .collect()
+    }
+
+    /// Classify every cell of the level in row-major order, telling walls and crates apart from
+    /// floor the worker can or cannot reach without pushing crates.
+    pub fn access_map(&self) -> Vec<AccessKind> {
+        let reachable: HashSet<_> = self.reachable_cells().into_iter().collect();
+
+        self.bounds()
+            .iter()
+            .map(|pos| {
+                if !self.is_interior(pos) {
+                    AccessKind::Wall
+                } else if self.is_crate(pos) {
+                    AccessKind::Crate
+                } else if reachable.contains(&pos) {
+                    AccessKind::ReachableFloor
+                } else {
+                    AccessKind::UnreachableFloor
+                }
+            })
+            .collect()
+    }
+
+    /// Compute the distance of each cell to `to` using a breadth-first search that stops once
+    /// the worker is reached. Return `None` if the worker cannot reach `to`.
+    fn distances_to(&self, to: Position) -> Option<Vec<usize>> {
+        let mut distances = vec![::std::usize::MAX; self.columns() * self.rows()];
         distances[self.index(to)] = 0;
 
-        let mut path_exists = false;
         let mut queue = VecDeque::with_capacity(500);
         queue.push_back(to);
 
         while let Some(pos) = queue.pop_front() {
-            if pos == self.dynamic.worker_position {
-                path_exists = true;
-                break;
+            if pos == self.dynamic.worker_position() {
+                return Some(distances);
             }
 
             // Is there a neighbour of pos to which we do not currently know the shortest path?
@@ -51,63 +139,232 @@ impl CurrentLevel {
             }
         }
 
-        if !path_exists {
-            self.notify(&Event::NoPathFound);
-            return None;
+        None
+    }
+
+    /// Follow the given path, if any. Return `None` if the movement was cancelled before
+    /// reaching the end of the path.
+    pub fn follow_path(&mut self, path: Path) -> Option<()> {
+        assert_eq!(self.dynamic.worker_position(), path.start);
+        for (i, Move { direction, .. }) in path.steps.into_iter().enumerate() {
+            if i != 0 && self.is_cancelled() {
+                return None;
+            }
+            let is_ok = self.try_move(direction).is_ok();
+            assert!(is_ok);
         }
+        Some(())
+    }
 
-        let mut path = Path {
-            start: self.dynamic.worker_position,
-            steps: vec![],
-        };
+    /// Try to find a way to move the crate at `from` to `to`.
+    pub fn find_path_with_crate(&self, from: Position, to: Position) -> Option<Path> {
+        self.is_valid_for_path_with_crate(from, to)?;
 
-        // Move worker along the path
-        let mut pos = self.dynamic.worker_position;
-        while pos != to {
-            for neighbour in self.empty_neighbours(pos) {
-                if distances[self.index(neighbour)] < distances[self.index(pos)] {
-                    if let DirectionResult::Neighbour { direction } = direction(pos, neighbour) {
-                        pos = neighbour;
-                        path.steps.push(Move {
-                            direction,
-                            moves_crate: false,
-                        });
-                    } else {
-                        unreachable!();
-                    }
+        let graph = self.build_graph(from);
+        graph.find_crate_path(from, to)
+    }
+
+    /// For each crate, count the cells it could be pushed to if all other crates stayed where they
+    /// are, and sum these numbers. Only pushes the worker can get into position for are taken
+    /// into account. The lower the result, the more constrained the level is.
+    pub fn total_crate_mobility(&self) -> usize {
+        self.dynamic
+            .crates
+            .keys()
+            .map(|&pos| self.crate_mobility(pos))
+            .sum()
+    }
+
+    /// The number of cells the worker could push the crate at `from` to while all other crates
+    /// stay where they are. Like `find_optimal_path_with_crate`, this searches the states made up
+    /// of the crate's and the worker's position.
+    fn crate_mobility(&self, from: Position) -> usize {
+        let start = (from, self.dynamic.worker_position());
+        let mut visited = HashSet::new();
+        visited.insert(start);
+        let mut queue = VecDeque::new();
+        queue.push_back(start);
+        let mut crate_positions = HashSet::new();
+
+        while let Some((crate_pos, worker_pos)) = queue.pop_front() {
+            crate_positions.insert(crate_pos);
+            for &direction in &DIRECTIONS {
+                let new_crate_pos = crate_pos.neighbour(direction);
+                let behind = crate_pos.neighbour(direction.reverse());
+                // After the push, the worker stands where the crate used to be.
+                let new_state = (new_crate_pos, crate_pos);
+                if self.is_free_for_crate_at(new_crate_pos, from, crate_pos)
+                    && !visited.contains(&new_state)
+                    && self
+                        .walking_distance(worker_pos, behind, from, crate_pos)
+                        .is_some()
+                {
+                    visited.insert(new_state);
+                    queue.push_back(new_state);
                 }
             }
         }
 
-        Some(path)
+        crate_positions.len() - 1
     }
 
-    /// Follow the given path, if any.
-    pub fn follow_path(&mut self, path: Path) {
-        assert_eq!(self.dynamic.worker_position, path.start);
-        for Move { direction, .. } in path.steps {
-            let is_ok = self.try_move(direction).is_ok();
-            assert!(is_ok);
+    /// A cheap check for levels that can no longer be solved. Return true if a crate that is not
+    /// on a goal is frozen, or if there are fewer empty goals any such crate can be pushed onto
+    /// than there are crates that still need a goal. This is only a heuristic: a return value of
+    /// false does not mean the level can be solved.
+    pub fn is_obviously_unsolvable(&self) -> bool {
+        let off_goal = |pos: &Position| self.background(*pos) != Background::Goal;
+        if self.frozen_crates().iter().any(off_goal) {
+            return true;
         }
+
+        let crates: Vec<_> = self
+            .dynamic
+            .crates
+            .keys()
+            .filter(|pos| off_goal(pos))
+            .collect();
+        let graphs: Vec<_> = crates.iter().map(|&&pos| self.build_graph(pos)).collect();
+        let reachable_goals = self
+            .background
+            .goals
+            .iter()
+            .filter(|&&goal| self.is_empty(goal))
+            .filter(|goal| {
+                graphs
+                    .iter()
+                    .any(|graph| graph.neighbours.contains_key(goal))
+            })
+            .count();
+
+        crates.len() > reachable_goals
     }
 
-    /// Try to find a way to move the crate at `from` to `to`.
-    pub fn find_path_with_crate(&self, from: Position, to: Position) -> Option<Path> {
+    /// Like `find_path_with_crate`, but also take into account how far the worker has to walk
+    /// between pushes. This searches the states made up of the crate's and the worker's position,
+    /// preferring the cheapest one according to `objective`.
+    pub fn find_optimal_path_with_crate(
+        &self,
+        from: Position,
+        to: Position,
+        objective: Objective,
+    ) -> Option<Path> {
         self.is_valid_for_path_with_crate(from, to)?;
 
-        let graph = self.build_graph(from);
-        graph.find_crate_path(from, to)
+        // Costs are stored as (moves, pushes) and compared according to `objective`.
+        let key = |(moves, pushes): (usize, usize)| match objective {
+            Objective::MinPushes => (pushes, moves),
+            Objective::MinMoves => (moves, pushes),
+        };
+
+        let start = (from, self.dynamic.worker_position());
+        let mut costs = HashMap::new();
+        let mut predecessors: HashMap<_, (_, Direction)> = HashMap::new();
+        let mut queue = BinaryHeap::new();
+        costs.insert(start, (0, 0));
+        queue.push(Reverse((
+            key((0, 0)),
+            self.index(start.0),
+            self.index(start.1),
+        )));
+
+        while let Some(Reverse((_, crate_index, worker_index))) = queue.pop() {
+            let state = (self.position(crate_index), self.position(worker_index));
+            let (crate_pos, worker_pos) = state;
+            if crate_pos == to {
+                let mut steps = vec![];
+                let mut current = state;
+                while let Some(&(previous, direction)) = predecessors.get(&current) {
+                    steps.push(Move::new(direction, true));
+                    current = previous;
+                }
+                steps.reverse();
+                return Some(Path { start: from, steps });
+            }
+
+            let (moves, pushes) = costs[&state];
+            for &direction in &DIRECTIONS {
+                let new_crate_pos = crate_pos.neighbour(direction);
+                let behind = crate_pos.neighbour(direction.reverse());
+                if !self.is_free_for_crate_at(new_crate_pos, from, crate_pos) {
+                    continue;
+                }
+                let walk = match self.walking_distance(worker_pos, behind, from, crate_pos) {
+                    Some(walk) => walk,
+                    None => continue,
+                };
+
+                let new_state = (new_crate_pos, crate_pos);
+                let new_cost = (moves + walk + 1, pushes + 1);
+                let is_cheaper = match costs.get(&new_state) {
+                    Some(&cost) => key(new_cost) < key(cost),
+                    None => true,
+                };
+                if is_cheaper {
+                    costs.insert(new_state, new_cost);
+                    predecessors.insert(new_state, (state, direction));
+                    queue.push(Reverse((
+                        key(new_cost),
+                        self.index(new_crate_pos),
+                        self.index(crate_pos),
+                    )));
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Is `pos` free while the crate which started out at `from` has been moved to `crate_pos`?
+    fn is_free_for_crate_at(&self, pos: Position, from: Position, crate_pos: Position) -> bool {
+        pos != crate_pos && (pos == from || self.is_empty(pos))
+    }
+
+    /// The number of steps the worker needs to get from `start` to `target` without pushing any
+    /// crates while the crate which started out at `from` is at `crate_pos`.
+    fn walking_distance(
+        &self,
+        start: Position,
+        target: Position,
+        from: Position,
+        crate_pos: Position,
+    ) -> Option<usize> {
+        if !self.is_free_for_crate_at(target, from, crate_pos) {
+            return None;
+        }
+
+        let mut distances = HashMap::new();
+        distances.insert(start, 0);
+        let mut queue = VecDeque::new();
+        queue.push_back(start);
+
+        while let Some(pos) = queue.pop_front() {
+            let distance = distances[&pos];
+            if pos == target {
+                return Some(distance);
+            }
+            for neighbour in DIRECTIONS.iter().map(|&dir| pos.neighbour(dir)) {
+                if self.is_free_for_crate_at(neighbour, from, crate_pos)
+                    && !distances.contains_key(&neighbour)
+                {
+                    distances.insert(neighbour, distance + 1);
+                    queue.push_back(neighbour);
+                }
+            }
+        }
+
+        None
     }
 
     fn move_worker_into_position(&mut self, crate_position: Position, r#move: &Move) -> Option<()> {
         let worker_pos = crate_position.neighbour(r#move.direction.reverse());
         let path = self.find_path(worker_pos)?;
-        self.follow_path(path);
-        Some(())
+        self.follow_path(path)
     }
 
     pub fn push_crate_along_path(&mut self, crate_path: Path) -> Option<()> {
         assert!(!crate_path.steps.is_empty());
+        self.cancel.reset();
 
         self.move_worker_into_position(crate_path.start, &crate_path.steps[0])?;
         self.try_move(crate_path.steps[0].direction).ok().unwrap();
@@ -115,7 +372,7 @@ impl CurrentLevel {
         for i in 1..crate_path.steps.len() {
             let crate_position = self
                 .dynamic
-                .worker_position
+                .worker_position()
                 .neighbour(self.worker_direction());
             self.move_worker_into_position(crate_position, &crate_path.steps[i])?;
             self.try_move(crate_path.steps[i].direction).ok().unwrap();
@@ -124,6 +381,27 @@ impl CurrentLevel {
         Some(())
     }
 
+    /// Walk to the cell behind the crate at `crate_pos` and push the crate one step in the given
+    /// direction. Emit `Event::NoPathFound` if the worker cannot get behind the crate.
+    pub fn nudge_crate(&mut self, crate_pos: Position, direction: Direction) -> Option<()> {
+        if !self.dynamic.crates.contains_key(&crate_pos) {
+            warn!(
+                "Cannot nudge crate at ({},{}): no crate there",
+                crate_pos.x, crate_pos.y
+            );
+            return None;
+        }
+
+        let worker_pos = crate_pos.neighbour(direction.reverse());
+        self.cancel.reset();
+        if self.dynamic.worker_position() != worker_pos && !self.is_empty(worker_pos) {
+            self.notify(&Event::NoPathFound);
+            return None;
+        }
+        self.move_worker_into_position(crate_pos, &Move::new(direction, true))?;
+        self.try_move(direction).ok()
+    }
+
     /// Create a graph of cells a crate `starting_from` can be moved to.
     fn build_graph(&self, starting_from: Position) -> Graph<Position> {
         let mut neighbours: HashMap<Position, Vec<_>> = HashMap::new();
@@ -205,6 +483,31 @@ mod tests {
     use super::*;
     use crate::position::Position;
 
+    #[test]
+    fn prefer_straight_paths() {
+        let s = "#########\n\
+                 #@      #\n\
+                 #       #\n\
+                 #       #\n\
+                 #      $#\n\
+                 #      .#\n\
+                 #########";
+        let mut sut: CurrentLevel = Level::parse(0, s).unwrap().into();
+
+        let to = Position::new(5, 4);
+        let path = sut.find_path(to).unwrap();
+        assert_eq!(path.steps.len(), 7);
+        let turns = path
+            .steps
+            .windows(2)
+            .filter(|w| w[0].direction != w[1].direction)
+            .count();
+        assert_eq!(turns, 1);
+
+        sut.follow_path(path);
+        assert_eq!(sut.worker_position(), to);
+    }
+
     #[test]
     fn cannot_move_into_wall() {
         let s = "#####\n\
@@ -269,7 +572,7 @@ mod tests {
 
         sut.push_crate_along_path(path);
 
-        assert_eq!(sut.dynamic.worker_position, Position { x: 19, y: 1 });
+        assert_eq!(sut.dynamic.worker_position(), Position { x: 19, y: 1 });
     }
 
     #[test]
@@ -303,6 +606,160 @@ mod tests {
 
         sut.push_crate_along_path(path);
 
-        assert_eq!(sut.dynamic.worker_position, Position { x: 3, y: 2 });
+        assert_eq!(sut.dynamic.worker_position(), Position { x: 3, y: 2 });
+    }
+
+    #[test]
+    fn path_length_matches_path() {
+        let collection = crate::collection::Collection::parse("original").unwrap();
+        let mut sut: CurrentLevel = collection.first_level().clone().into();
+
+        let mut reachable = 0;
+        for index in 0..sut.columns() * sut.rows() {
+            let pos = sut.position(index);
+            if !sut.is_empty(pos) {
+                continue;
+            }
+            let length = sut.path_length(pos);
+            assert_eq!(length, sut.find_path(pos).map(|path| path.steps.len()));
+            if length.is_some() {
+                reachable += 1;
+            }
+        }
+        assert!(reachable > 10);
+        assert_eq!(sut.path_length(sut.worker_position()), Some(0));
+    }
+
+    #[test]
+    fn nudge_crate() {
+        let s = "#######\n\
+                 #  $ .#\n\
+                 #@    #\n\
+                 #######";
+        let mut sut: CurrentLevel = Level::parse(0, s).unwrap().into();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        sut.subscribe(sender);
+
+        let crate_pos = Position::new(3, 1);
+        assert!(sut.nudge_crate(crate_pos, Direction::Right).is_some());
+        assert_eq!(sut.crate_id_at(Position::new(4, 1)), Some(0));
+        assert_eq!(sut.worker_position(), crate_pos);
+        assert_eq!(sut.number_of_pushes(), 1);
+
+        // There is a wall above the crate, so it cannot be pushed down
+        let _ = receiver.try_iter().count();
+        assert!(sut
+            .nudge_crate(Position::new(4, 1), Direction::Down)
+            .is_none());
+        assert!(receiver.try_iter().any(|e| matches!(e, Event::NoPathFound)));
+        assert_eq!(sut.worker_position(), crate_pos);
+    }
+
+    #[test]
+    fn reachable_cells() {
+        let s = "#######\n\
+                 #@ #  #\n\
+                 # $*. #\n\
+                 #######";
+        let sut: CurrentLevel = Level::parse(0, s).unwrap().into();
+
+        let expected = vec![
+            Position::new(1, 1),
+            Position::new(2, 1),
+            Position::new(1, 2),
+        ];
+        assert_eq!(sut.reachable_cells(), expected);
+    }
+
+    #[test]
+    fn crate_mobility() {
+        let corridor = "#######\n\
+                        #@$  .#\n\
+                        #######";
+        let corridor: CurrentLevel = Level::parse(0, corridor).unwrap().into();
+        // The worker cannot get to the right of the crate to push it back to the left
+        assert_eq!(corridor.total_crate_mobility(), 3);
+
+        let open = "#######\n\
+                    #     #\n\
+                    # @$ .#\n\
+                    #     #\n\
+                    #######";
+        let open: CurrentLevel = Level::parse(0, open).unwrap().into();
+        assert!(open.total_crate_mobility() > corridor.total_crate_mobility());
+    }
+
+    #[test]
+    fn obviously_unsolvable() {
+        let s = "#######\n\
+                 #@$ . #\n\
+                 #######";
+        let mut lvl: CurrentLevel = Level::parse(0, s).unwrap().into();
+        lvl.try_move(Direction::Right).unwrap();
+        assert!(!lvl.is_obviously_unsolvable());
+
+        // A crate in a corner
+        let s = "######\n\
+                 #$  .#\n\
+                 # @  #\n\
+                 ######";
+        let lvl: CurrentLevel = Level::parse(0, s).unwrap().into();
+        assert!(lvl.is_obviously_unsolvable());
+
+        // A crate that can only be moved along the wall, away from the goal
+        let s = "#######\n\
+                 #  $  #\n\
+                 #@   .#\n\
+                 #######";
+        let lvl: CurrentLevel = Level::parse(0, s).unwrap().into();
+        assert!(lvl.frozen_crates().is_empty());
+        assert!(lvl.is_obviously_unsolvable());
+    }
+
+    #[test]
+    fn optimal_crate_path() {
+        // Two pushes suffice, but only after walking around to the other side of the crate.
+        let s = "#######\n\
+                 # #  ##\n\
+                 # @$  #\n\
+                 # .   #\n\
+                 #######";
+        let lvl = Level::parse(0, s).unwrap();
+        let from = Position::new(3, 2);
+        let to = Position::new(2, 3);
+        let solve = |objective| {
+            let mut sut: CurrentLevel = lvl.clone().into();
+            sut.move_crate_to_target_optimal(from, to, objective)
+                .unwrap();
+            assert!(sut.is_finished());
+            (sut.number_of_moves(), sut.number_of_pushes())
+        };
+
+        assert_eq!(solve(Objective::MinPushes), (10, 2));
+        assert_eq!(solve(Objective::MinMoves), (8, 4));
+    }
+
+    #[test]
+    fn access_map() {
+        let s = "#########\n\
+                 #@ $.#* #\n\
+                 #    #  #\n\
+                 #########";
+        let sut: CurrentLevel = Level::parse(0, s).unwrap().into();
+        let map = sut.access_map();
+        let access = |x, y| map[sut.bounds().index(Position::new(x, y))];
+
+        assert_eq!(map.len(), 9 * 4);
+        assert_eq!(access(0, 0), AccessKind::Wall);
+        assert_eq!(access(5, 1), AccessKind::Wall);
+        assert_eq!(access(1, 1), AccessKind::ReachableFloor);
+        assert_eq!(access(4, 1), AccessKind::ReachableFloor);
+        assert_eq!(access(3, 1), AccessKind::Crate);
+        assert_eq!(access(6, 1), AccessKind::Crate);
+
+        // The room on the right is cut off by a wall
+        for &(x, y) in &[(7, 1), (6, 2), (7, 2)] {
+            assert_eq!(access(x, y), AccessKind::UnreachableFloor);
+        }
     }
 }
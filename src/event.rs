@@ -18,7 +18,9 @@ pub enum Event {
         worker_direction: Direction,
         crates: HashMap<Position, usize>,
     },
+    /// The worker with the given index moved. Most levels only have one worker, whose index is 0.
     MoveWorker {
+        worker_id: usize,
         from: Position,
         to: Position,
         direction: Direction,
@@ -28,12 +30,49 @@ pub enum Event {
         from: Position,
         to: Position,
     },
+
+    /// Several steps taken at once, sent instead of `MoveWorker` and `MoveCrate` events if
+    /// batching is enabled. `worker_path` lists the positions the worker with index `worker_id`
+    /// moved to in order, and `crate_moves` contains each crate's id together with where it was
+    /// moved from and to.
+    BatchMove {
+        worker_id: usize,
+        worker_path: Vec<Position>,
+        crate_moves: Vec<(usize, Position, Position)>,
+    },
     NothingToRedo,
     NothingToUndo,
     LevelFinished(UpdateResponse),
+
+    /// How the level that was just finished has been solved. Sent right after `LevelFinished`.
+    LevelStats {
+        moves: usize,
+        pushes: usize,
+        time_ms: u64,
+    },
     EndOfCollection,
 
+    /// The last level of the collection has been solved for the first time.
+    CollectionSolved {
+        name: String,
+    },
+
     MacroDefined,
+    MacroRecordingStarted {
+        slot: u8,
+    },
+    MacroRecordingStopped {
+        slot: u8,
+        length: usize,
+    },
+
+    /// A movement consisting of several steps was stopped early.
+    MovementCancelled,
+
+    /// The worker has been next to these cells for the first time.
+    CellsRevealed {
+        positions: Vec<Position>,
+    },
 
     NoPathfindingWhilePushing,
     CannotMove(WithCrate, Obstacle),
@@ -48,9 +87,16 @@ impl Event {
             InitialLevelState { .. }
             | MoveWorker { .. }
             | MoveCrate { .. }
+            | BatchMove { .. }
             | LevelFinished(_)
+            | LevelStats { .. }
             | EndOfCollection
-            | MacroDefined => false,
+            | CollectionSolved { .. }
+            | MacroDefined
+            | MacroRecordingStarted { .. }
+            | MacroRecordingStopped { .. }
+            | MovementCancelled
+            | CellsRevealed { .. } => false,
             _ => true,
         }
     }
@@ -1,13 +1,15 @@
 use std::convert::TryFrom;
 use std::sync::mpsc::{Receiver, Sender};
+use std::time::{Duration, Instant};
 
 use crate::collection::*;
 use crate::command::*;
-use crate::current_level::CurrentLevel;
+use crate::current_level::{CancelHandle, CurrentLevel};
 use crate::direction::Direction;
 use crate::event::*;
 use crate::level::Level;
 use crate::macros::Macros;
+use crate::move_::Move;
 use crate::position::Position;
 use crate::save::*;
 use crate::util::SokobanError;
@@ -19,6 +21,9 @@ pub enum NextLevelError {
 
     /// Cannot move past the last level of a collection.
     EndOfCollection,
+
+    /// There is no level with the requested rank in this collection.
+    NoSuchLevel,
 }
 
 pub struct Game {
@@ -34,14 +39,35 @@ pub struct Game {
     /// What levels have been solved and with how many moves/pushes.
     state: CollectionState,
 
+    /// The player's preferences for the current collection.
+    settings: CollectionSettings,
+
     /// Macros
     macros: Macros,
 
     listeners: Listeners,
 
     receiver: Option<Receiver<Command>>,
+
+    /// Go to the next level automatically after solving one.
+    auto_advance: bool,
+
+    /// When the last snapshot for crash recovery was written.
+    last_snapshot: Instant,
+
+    /// Report movements consisting of several steps as a single event.
+    batch_moves: bool,
+
+    /// Go back to the first collection when calling `next_collection` on the last one.
+    wrap_collections: bool,
+
+    /// Shared by all levels so handles given out by `cancel_handle` keep working.
+    cancel: CancelHandle,
 }
 
+/// How often to write a `GameSnapshot` while the player is making moves.
+const SNAPSHOT_INTERVAL: Duration = Duration::from_secs(30);
+
 #[derive(Default)]
 struct Listeners {
     moves: Vec<Sender<Event>>,
@@ -81,12 +107,37 @@ impl Game {
     fn set_current_level(&mut self, level: &Level, rank: usize) {
         self.rank = rank;
         self.current_level = level.into();
+        self.current_level.set_batch_moves(self.batch_moves);
+        self.current_level.set_cancel_handle(self.cancel.clone());
         for listener in &self.listeners.moves {
             self.current_level.subscribe(listener.clone());
         }
+        self.remember_rank();
+        self.discard_snapshot();
         self.on_load_level();
     }
 
+    /// Store the current rank in the settings so the game can continue here next time.
+    fn remember_rank(&mut self) {
+        if self.settings.last_rank != self.rank {
+            self.settings.last_rank = self.rank;
+            self.save_settings();
+        }
+    }
+
+    fn save_settings(&self) {
+        if let Err(e) = self.settings.save(self.collection.short_name()) {
+            warn!("Failed to save settings: {}", e);
+        }
+    }
+
+    /// Go to the level the player was playing most recently, if it is known.
+    fn resume_level(&mut self, last_rank: usize) {
+        if last_rank != 0 && last_rank != self.rank && self.goto_level(last_rank).is_err() {
+            warn!("Cannot resume level #{}", last_rank);
+        }
+    }
+
     fn on_load_level(&self) {
         let rank = self.rank();
         let lvl = self.get_level(rank);
@@ -104,33 +155,85 @@ impl Game {
 }
 
 impl Game {
-    pub fn new(collection: Collection) -> Self {
+    /// Start playing the given collection. Fails if the collection does not contain any levels.
+    pub fn new(collection: Collection) -> Result<Self, SokobanError> {
+        let first_level = collection
+            .first_level_checked()
+            .ok_or_else(|| SokobanError::EmptyCollection(collection.short_name().to_string()))?;
+        let cancel = CancelHandle::default();
+        let mut current_level: CurrentLevel = first_level.into();
+        current_level.set_cancel_handle(cancel.clone());
         let mut result = Game {
             rank: 1,
             name: collection.short_name().to_string(),
-            current_level: collection.first_level().into(),
+            current_level,
             state: CollectionState::load(collection.short_name()),
+            settings: CollectionSettings::load(collection.short_name()),
             macros: Macros::new(),
             collection,
             listeners: Listeners::new(),
             receiver: None,
+            auto_advance: false,
+            last_snapshot: Instant::now(),
+            batch_moves: false,
+            wrap_collections: true,
+            cancel,
         };
 
+        let last_rank = result.settings.last_rank;
         result.load_state(true);
+        result.resume_level(last_rank);
 
-        result
+        Ok(result)
     }
 
-    /// Load a collection by name.
+    /// Load a collection by name. If it cannot be loaded or does not contain any levels, the
+    /// current collection is kept.
     pub fn set_collection(&mut self, name: &str) -> Result<(), SokobanError> {
+        let collection = Collection::parse(name)?;
+        let level = collection
+            .first_level_checked()
+            .ok_or_else(|| SokobanError::EmptyCollection(name.to_string()))?
+            .clone();
+        let settings = CollectionSettings::load(name);
+        let last_rank = settings.last_rank;
+
         self.name = name.into();
-        self.collection = Collection::parse(name)?;
-        let level = self.collection.first_level().clone();
+        self.collection = collection;
+        self.settings = settings;
         self.set_current_level(&level, 1);
         self.load_state(true);
+        self.resume_level(last_rank);
         Ok(())
     }
 
+    /// Load the collection following the current one in the list of all collections, sorted by
+    /// name. After the last collection, go back to the first one or fail, depending on
+    /// `set_wrap_collections`.
+    pub fn next_collection(&mut self) -> Result<(), SokobanError> {
+        let names = crate::collection_names()?;
+        let current = self.collection.short_name().to_string();
+        let next = match names.iter().position(|name| *name == current) {
+            Some(i) if i + 1 < names.len() => &names[i + 1],
+            // Collections loaded from elsewhere are not in the list, so start at the beginning.
+            None if !names.is_empty() => &names[0],
+            _ if self.wrap_collections && !names.is_empty() => &names[0],
+            _ => return Err(SokobanError::NoNextCollection(current)),
+        };
+        let next = next.clone();
+        self.set_collection(&next)
+    }
+
+    /// Choose whether macros may reset the level.
+    pub fn set_macros_allow_management(&mut self, allow_management: bool) {
+        self.macros.allow_management = allow_management;
+    }
+
+    /// Choose whether `next_collection` starts over at the first collection after the last.
+    pub fn set_wrap_collections(&mut self, wrap_collections: bool) {
+        self.wrap_collections = wrap_collections;
+    }
+
     /// Execute a command from the front end. Load new collections or pass control to
     /// `Collection::execute`.
     pub fn execute(&mut self) {
@@ -149,6 +252,59 @@ impl Game {
                 self.execute_helper(&cmd, false)
             }
         }
+
+        if self.last_snapshot.elapsed() >= SNAPSHOT_INTERVAL {
+            self.save_snapshot();
+        }
+    }
+
+    /// Write a snapshot for crash recovery.
+    fn save_snapshot(&mut self) {
+        self.last_snapshot = Instant::now();
+        if let Err(e) = self.snapshot().save() {
+            warn!("Failed to save snapshot: {}", e);
+        }
+    }
+
+    /// Delete the snapshot as it no longer matches the current level.
+    fn discard_snapshot(&self) {
+        if let Err(e) = GameSnapshot::remove() {
+            warn!("Failed to remove snapshot: {}", e);
+        }
+    }
+
+    /// Capture the collection, the current level and the moves made so far, so the game can be
+    /// restored after a crash.
+    pub fn snapshot(&self) -> GameSnapshot {
+        GameSnapshot {
+            collection: self.collection.short_name().to_string(),
+            rank: self.rank,
+            number_of_moves: self.current_level.number_of_moves(),
+            moves: self.current_level.all_moves_to_string(),
+        }
+    }
+
+    /// Reload the collection stored in the snapshot and replay the moves made in its current
+    /// level. The snapshot is saved again right away, so it is not lost if the game crashes
+    /// again before the next regular snapshot.
+    pub fn restore(snapshot: &GameSnapshot) -> Result<Self, SokobanError> {
+        let mut game = Game::new(Collection::parse(&snapshot.collection)?)?;
+        if snapshot.rank == 0 || snapshot.rank > game.collection.number_of_levels() {
+            warn!("Snapshot refers to non-existent level #{}", snapshot.rank);
+            return Ok(game);
+        }
+
+        // Start from scratch rather than from the moves stored in the collection state.
+        let level = game.get_level(snapshot.rank);
+        game.set_current_level(&level, snapshot.rank);
+        let level_state = LevelState::Started {
+            number_of_moves: snapshot.number_of_moves,
+            moves: snapshot.moves.clone(),
+        };
+        restore_moves(&mut game.current_level, &level_state);
+        game.save_snapshot();
+
+        Ok(game)
     }
 
     /// Is the current level the last one in this collection?
@@ -201,6 +357,52 @@ impl Game {
         self.current_level.number_of_pushes()
     }
 
+    /// Choose whether to go to the next level automatically once the current one is solved.
+    pub fn set_auto_advance(&mut self, auto_advance: bool) {
+        self.auto_advance = auto_advance;
+    }
+
+    /// Choose whether movements consisting of several steps, e.g. walking to a far away cell,
+    /// are reported as a single `Event::BatchMove` instead of one event per step.
+    pub fn set_batch_moves(&mut self, batch_moves: bool) {
+        self.batch_moves = batch_moves;
+        self.current_level.set_batch_moves(batch_moves);
+    }
+
+    /// If auto-advance is enabled and the current level has been solved, go to the next level.
+    /// This is meant to be called by the front end once it is done animating the final move.
+    /// Return true if and only if a new level was loaded.
+    pub fn advance_if_auto(&mut self) -> bool {
+        self.auto_advance && self.current_level.is_finished() && self.next_level().is_ok()
+    }
+
+    /// Stop the movement currently being performed, e.g. walking to a far away cell, after the
+    /// current step. Requests made while no such movement is in progress are ignored.
+    pub fn request_cancel(&self) {
+        self.cancel.cancel();
+    }
+
+    /// A handle for cancelling movements from another thread, e.g. while `execute` is busy
+    /// walking to a far away cell. It stays valid when a different level is loaded.
+    pub fn cancel_handle(&self) -> CancelHandle {
+        self.cancel.clone()
+    }
+
+    /// Stop counting the time spent on the current level.
+    pub fn pause_timer(&mut self) {
+        self.current_level.pause_timer();
+    }
+
+    /// Continue counting the time spent on the current level.
+    pub fn resume_timer(&mut self) {
+        self.current_level.resume_timer();
+    }
+
+    /// Is the current level still (or again) in its initial state?
+    pub fn is_pristine(&self) -> bool {
+        self.current_level.is_pristine()
+    }
+
     /// The collections full name
     pub fn name(&self) -> &str {
         self.collection.name()
@@ -239,8 +441,19 @@ impl Game {
     fn execute_movement(&mut self, movement: &Movement) {
         use crate::Movement::*;
 
+        // Going back and forth in the history of moves does not count as a command.
+        match *movement {
+            Undo | Redo | UndoAll | RedoAll | UndoToLastPush | Checkpoint | RestoreCheckpoint => {}
+            _ => self.current_level.count_command(),
+        }
+
         match *movement {
             Step { direction } => self.current_level.step(direction),
+            Pull { direction } => {
+                if let Err(err) = self.current_level.pull(direction) {
+                    self.listeners.notify_move(&err.into());
+                }
+            }
             WalkTillObstacle { direction } => {
                 self.current_level.move_as_far_as_possible(direction, false)
             }
@@ -248,17 +461,35 @@ impl Game {
                 self.current_level.move_as_far_as_possible(direction, true)
             }
             WalkTowards { position } => {
-                self.current_level.move_to(position, false);
+                self.current_level.move_to(position, false, None);
             }
             PushTowards { position } => {
-                self.current_level.move_to(position, true);
+                self.current_level.move_to(position, true, None);
             }
             WalkToPosition { position } => {
-                self.current_level.move_to(position, false);
+                self.current_level.move_to(position, false, None);
             }
             MoveCrateToTarget { from, to } => {
                 self.current_level.move_crate_to_target(from, to);
             }
+            PushCrateToNearestGoal { from } => {
+                self.current_level.push_crate_to_nearest_goal(from);
+            }
+            AssignCrateToGoal {
+                crate_pos,
+                goal_pos,
+            } => {
+                self.current_level.assign_crate_to_goal(crate_pos, goal_pos);
+            }
+            NudgeCrate {
+                crate_pos,
+                direction,
+            } => {
+                self.current_level.nudge_crate(crate_pos, direction);
+            }
+            TeleportWorker { position } => {
+                self.current_level.teleport_worker(position);
+            }
 
             Undo => {
                 self.current_level.undo();
@@ -266,6 +497,15 @@ impl Game {
             Redo => {
                 self.current_level.redo();
             }
+            UndoAll => while self.current_level.undo() {},
+            RedoAll => while self.current_level.redo() {},
+            UndoToLastPush => self.current_level.undo_to_last_push(),
+            Checkpoint => self.current_level.set_checkpoint(),
+            RestoreCheckpoint => {
+                if !self.current_level.restore_checkpoint() {
+                    warn!("Cannot restore checkpoint");
+                }
+            }
         }
     }
 
@@ -275,13 +515,23 @@ impl Game {
         match *macro_command {
             Execute(slot) => self.execute_macro(slot),
             Record(slot) => {
+                self.stop_recording_macro();
                 self.macros.start_recording(slot);
+                self.listeners
+                    .notify_move(&Event::MacroRecordingStarted { slot });
             }
-            Store => {
-                let len = self.macros.stop_recording();
-                if len != 0 {
-                    self.listeners.notify_move(&Event::MacroDefined);
-                }
+            Store => self.stop_recording_macro(),
+        }
+    }
+
+    /// Store the macro currently being recorded, if any, and tell the listeners about it.
+    fn stop_recording_macro(&mut self) {
+        if let Some(slot) = self.macros.recording_slot() {
+            let length = self.macros.stop_recording();
+            self.listeners
+                .notify_move(&Event::MacroRecordingStopped { slot, length });
+            if length != 0 {
+                self.listeners.notify_move(&Event::MacroDefined);
             }
         }
     }
@@ -307,14 +557,13 @@ impl Game {
         }
 
         if self.current_level.is_finished() {
-            if self.rank() == self.collection.number_of_levels() {
+            let newly_solved_collection =
+                self.rank() == self.collection.number_of_levels() && !self.state.collection_solved;
+            if newly_solved_collection {
                 self.state.collection_solved = true;
             }
             if !is_finished {
-                let len = self.macros.stop_recording();
-                if len != 0 {
-                    self.listeners.notify_move(&Event::MacroDefined);
-                }
+                self.stop_recording_macro();
             }
 
             // TODO Emit the events in one of the move() functions?
@@ -327,12 +576,22 @@ impl Game {
                         .notify_move(&Event::LevelFinished(UpdateResponse::FirstTimeSolved));
                 }
             }
+            self.listeners.notify_move(&Event::LevelStats {
+                moves: self.current_level.number_of_moves(),
+                pushes: self.current_level.number_of_pushes(),
+                time_ms: self.current_level.active_time().as_millis() as u64,
+            });
+            if newly_solved_collection {
+                self.listeners.notify_move(&Event::CollectionSolved {
+                    name: self.collection.name().to_string(),
+                });
+            }
         }
     }
 
     fn execute_macro(&mut self, slot: u8) {
         // NOTE We have to clone the commands so we can borrow self mutably in the loop.
-        let cmds = self.macros.get(slot).to_owned();
+        let cmds = self.macros.get(slot);
         cmds.iter().for_each(|cmd| self.execute_helper(cmd, true));
     }
 
@@ -367,6 +626,23 @@ impl Game {
         }
     }
 
+    /// Jump to the level with the given rank. If that level has been started before, the moves
+    /// made so far are restored.
+    pub fn goto_level(&mut self, rank: usize) -> Result<(), NextLevelError> {
+        if rank == 0 || rank > self.collection.number_of_levels() {
+            return Err(NextLevelError::NoSuchLevel);
+        }
+
+        let level = self.get_level(rank);
+        self.set_current_level(&level, rank);
+
+        if let Some(level_state) = self.state.levels.get(rank - 1) {
+            restore_moves(&mut self.current_level, level_state);
+        }
+
+        Ok(())
+    }
+
     /// Go to the previous level unless this is already the first level in this collection.
     fn previous_level(&mut self) -> Result<(), ()> {
         let n = self.rank();
@@ -387,31 +663,10 @@ impl Game {
         );
 
         // DEBT this is horrible, clean it up
-        let state: CollectionState;
+        let mut state: CollectionState;
         if parse_levels {
             state = CollectionState::load(self.collection.short_name());
-            if !state.collection_solved {
-                let n = state.levels_finished();
-
-                let lvl = self.get_level(n + 1);
-                self.set_current_level(&lvl, n + 1);
-                self.rank = n + 1;
-
-                if n < state.number_of_levels() {
-                    if let LevelState::Started {
-                        number_of_moves,
-                        ref moves,
-                        ..
-                    } = state.levels[n]
-                    {
-                        let is_ok = self
-                            .current_level
-                            .execute_moves(number_of_moves, moves)
-                            .is_ok();
-                        assert!(is_ok);
-                    }
-                }
-            }
+            self.resume_from(&mut state);
         } else {
             state = CollectionState::load_stats(self.collection.short_name());
         }
@@ -424,6 +679,36 @@ impl Game {
         );
     }
 
+    /// Go to the first level not finished according to `state` and restore the moves saved for
+    /// it. The collection may have changed since `state` was saved, so the saved states of levels
+    /// that no longer exist are dropped and the rank is clamped to the number of levels.
+    fn resume_from(&mut self, state: &mut CollectionState) {
+        let number_of_levels = self.collection.number_of_levels();
+        if state.number_of_levels() > number_of_levels {
+            warn!(
+                "Saved state has {} levels, but collection {} only has {}",
+                state.number_of_levels(),
+                self.collection.short_name(),
+                number_of_levels
+            );
+            state.levels.truncate(number_of_levels);
+        }
+        if state.collection_solved || number_of_levels == 0 {
+            return;
+        }
+
+        let n = state.levels_finished();
+        let rank = (n + 1).min(number_of_levels);
+
+        let lvl = self.get_level(rank);
+        self.set_current_level(&lvl, rank);
+        self.rank = rank;
+
+        if n < state.number_of_levels() {
+            restore_moves(&mut self.current_level, &state.levels[n]);
+        }
+    }
+
     /// Save the state of this collection including the state of the current level.
     fn save(&mut self) -> Result<UpdateResponse, SaveError> {
         // TODO self should not be mut
@@ -432,15 +717,111 @@ impl Game {
             Ok(soln) => LevelState::new_solved(soln),
             _ => LevelState::new_unsolved(&self.current_level),
         };
+        let is_solved = level_state.is_finished();
         let response = self.state.update(rank - 1, level_state);
 
         self.state.save(self.collection.short_name())?;
+        // Saving an unfinished level for the first time is reported as FirstTimeSolved, too.
+        if is_solved && matches!(response, UpdateResponse::FirstTimeSolved) {
+            self.log_solve(rank);
+        }
         Ok(response)
     }
 
+    /// Append the level with the given rank to the solve log.
+    fn log_solve(&self, rank: usize) {
+        let mut log = SolveLog::load();
+        log.record(today(), self.collection.short_name(), rank);
+        if let Err(e) = log.save() {
+            warn!("Failed to save solve log: {}", e);
+        }
+    }
+
     pub fn is_solved(&self) -> bool {
         self.state.collection_solved
     }
+
+    /// The player's preferences for the current collection.
+    pub fn settings(&self) -> &CollectionSettings {
+        &self.settings
+    }
+
+    /// Choose the texture theme to use for the current collection.
+    pub fn set_theme(&mut self, theme: &str) {
+        self.settings.theme = theme.into();
+        self.save_settings();
+    }
+
+    /// The solution with the least moves found so far for the level with the given rank, if it
+    /// has been solved.
+    pub fn best_solution(&self, rank: usize) -> Option<&Solution> {
+        match self.state.levels.get(rank.checked_sub(1)?)? {
+            LevelState::Finished { least_moves, .. } => Some(least_moves),
+            LevelState::Started { .. } => None,
+        }
+    }
+
+    /// Write the best solution for the level with the given rank as a snapshot block in the .sok
+    /// format understood by YASC and most other Sokoban programs, i.e. the level title and the
+    /// number of moves and pushes followed by the solution in LURD format, at most 70 characters
+    /// per line.
+    pub fn export_yasc_solution(&self, rank: usize) -> Option<String> {
+        const LINE_LENGTH: usize = 70;
+
+        let solution = self.best_solution(rank)?;
+        let title = self
+            .collection
+            .levels()
+            .get(rank - 1)
+            .and_then(|level| level.title.clone())
+            .unwrap_or_else(|| format!("Level {}", rank));
+
+        let mut result = format!(
+            "Title: {}\nMoves: {}\nPushes: {}\n\nSolution/Moves\n",
+            title,
+            solution.number_of_moves(),
+            solution.number_of_pushes()
+        );
+        let steps: Vec<_> = solution.steps().chars().collect();
+        for line in steps.chunks(LINE_LENGTH) {
+            result.extend(line);
+            result.push('\n');
+        }
+        Some(result)
+    }
+
+    /// Suggest the next move if the moves made so far are the beginning of one of the solutions
+    /// stored for the current level.
+    pub fn hint(&self) -> Option<Move> {
+        let (least_moves, least_pushes) = match self.state.levels.get(self.rank() - 1)? {
+            LevelState::Finished {
+                least_moves,
+                least_pushes,
+            } => (least_moves, least_pushes),
+            LevelState::Started { .. } => return None,
+        };
+        let moves = self.current_level.moves_to_string();
+
+        [least_moves, least_pushes]
+            .iter()
+            .filter_map(|solution| solution.steps().strip_prefix(moves.as_str()))
+            .filter_map(|rest| rest.chars().next())
+            .find_map(|c| Move::try_from(c).ok())
+    }
+}
+
+/// If the given level has been started but not finished, replay the moves stored for it.
+fn restore_moves(current_level: &mut CurrentLevel, level_state: &LevelState) {
+    if let LevelState::Started {
+        number_of_moves,
+        ref moves,
+        ..
+    } = *level_state
+    {
+        if let Err(e) = current_level.execute_moves(number_of_moves, moves) {
+            error!("Failed to restore the moves stored for this level: {}", e);
+        }
+    }
 }
 
 #[cfg(test)]
@@ -463,7 +844,7 @@ mod tests {
     }
 
     fn setup_game(name: &str) -> (Game, Receiver<Event>) {
-        let mut game = Game::new(Collection::parse(name).unwrap());
+        let mut game = Game::new(Collection::parse(name).unwrap()).unwrap();
         let (sender, receiver) = channel();
         game.subscribe_moves(sender);
         (game, receiver)
@@ -533,6 +914,178 @@ mod tests {
         assert_eq!(game.current_level.number_of_pushes(), 1);
     }
 
+    #[test]
+    fn undo_and_redo_all() {
+        let lvl = Level::parse(0, "#######\n#@  $.#\n#######").unwrap();
+        let collection = Collection::from_levels("undo_all", std::slice::from_ref(&lvl));
+        let mut game = Game::new(collection).unwrap();
+        game.current_level.execute_moves(2, "rrR").unwrap();
+
+        game.execute_helper(&Command::Movement(Movement::UndoAll), false);
+        assert_eq!(game.worker_position(), lvl.worker_position);
+        assert_eq!(game.number_of_moves(), 0);
+
+        game.execute_helper(&Command::Movement(Movement::RedoAll), false);
+        assert!(game.current_level.is_finished());
+        assert_eq!(game.number_of_moves(), 3);
+    }
+
+    #[test]
+    fn retrieve_best_solution() {
+        let lvl = Level::parse(0, "######\n#@ $.#\n######").unwrap();
+        let collection = Collection::from_levels("best_solution", &[lvl]);
+        let mut game = Game::new(collection).unwrap();
+        assert!(game.best_solution(1).is_none());
+
+        for _ in 0..2 {
+            let direction = Direction::Right;
+            game.execute_helper(&Command::Movement(Movement::Step { direction }), false);
+        }
+        let solution = game.best_solution(1).unwrap();
+        assert_eq!(solution.steps(), "rR");
+        assert_eq!(solution.number_of_moves(), 2);
+        assert_eq!(solution.number_of_pushes(), 1);
+        assert!(crate::util::data_dir().join("best_solution.cbor").is_file());
+        assert!(game.best_solution(0).is_none());
+        assert!(game.best_solution(2).is_none());
+    }
+
+    #[test]
+    fn only_log_solved_levels() {
+        let lvl = Level::parse(0, "#######\n#@  $.#\n#######").unwrap();
+        let mut game = Game::new(Collection::from_levels("solve_log", &[lvl])).unwrap();
+        let step = Command::Movement(Movement::Step {
+            direction: Direction::Right,
+        });
+
+        game.execute_helper(&step, false);
+        game.execute_helper(&Command::LevelManagement(LevelManagement::Save), false);
+        assert!(SolveLog::load().entries().is_empty());
+
+        game.execute_helper(&step, false);
+        game.execute_helper(&step, false);
+        assert!(game.current_level.is_finished());
+        assert_eq!(SolveLog::load().entries().len(), 1);
+        assert!(!crate::util::data_dir().join("solvelog.cbor").exists());
+    }
+
+    #[test]
+    fn level_stats() {
+        let lvl = Level::parse(0, "######\n#@ $.#\n######").unwrap();
+        let mut game = Game::new(Collection::from_levels("level_stats", &[lvl])).unwrap();
+        let (sender, receiver) = channel();
+        game.subscribe_moves(sender);
+
+        for _ in 0..2 {
+            let direction = Direction::Right;
+            game.execute_helper(&Command::Movement(Movement::Step { direction }), false);
+        }
+        let stats = receiver.try_iter().find_map(|event| match event {
+            Event::LevelStats { moves, pushes, .. } => Some((moves, pushes)),
+            _ => None,
+        });
+        assert_eq!(stats, Some((2, 1)));
+    }
+
+    #[test]
+    fn hint_follows_solution() {
+        let lvl = Level::parse(0, "#######\n#@  $.#\n#######").unwrap();
+        let mut solved: CurrentLevel = lvl.clone().into();
+        solved.execute_moves(3, "rrR").unwrap();
+        let solution = Solution::try_from(&solved).unwrap();
+
+        let mut game = Game::new(Collection::from_levels("hint", &[lvl])).unwrap();
+        assert_eq!(game.hint(), None);
+        game.state.update(0, LevelState::new_solved(solution));
+        assert_eq!(game.hint(), Some(Move::new(Direction::Right, false)));
+
+        let step = |direction| Command::Movement(Movement::Step { direction });
+        game.execute_helper(&step(Direction::Right), false);
+        game.execute_helper(&step(Direction::Right), false);
+        assert_eq!(game.hint(), Some(Move::new(Direction::Right, true)));
+
+        game.execute_helper(&step(Direction::Left), false);
+        assert_eq!(game.hint(), None);
+    }
+
+    #[test]
+    fn export_solution() {
+        let lvl = Level::parse(0, "######\n#@ $.#\n######").unwrap();
+        let mut game = Game::new(Collection::from_levels("yasc", &[lvl])).unwrap();
+        assert!(game.export_yasc_solution(1).is_none());
+
+        for _ in 0..2 {
+            let direction = Direction::Right;
+            game.execute_helper(&Command::Movement(Movement::Step { direction }), false);
+        }
+        let exported = game.export_yasc_solution(1).unwrap();
+        assert_eq!(
+            exported,
+            "Title: Level 1\nMoves: 2\nPushes: 1\n\nSolution/Moves\nrR\n"
+        );
+    }
+
+    #[test]
+    fn auto_advance() {
+        let levels = [
+            Level::parse(0, "#####\n#@$.#\n#####").unwrap(),
+            Level::parse(1, "#####\n#.$@#\n#####").unwrap(),
+        ];
+        let mut game = Game::new(Collection::from_levels("auto_advance", &levels)).unwrap();
+        let step = |direction| Command::Movement(Movement::Step { direction });
+
+        game.execute_helper(&step(Direction::Right), false);
+        assert!(!game.advance_if_auto());
+        assert_eq!(game.rank(), 1);
+
+        game.set_auto_advance(true);
+        assert!(game.advance_if_auto());
+        assert_eq!(game.rank(), 2);
+
+        // The second level has not been solved yet, and it is the last one.
+        assert!(!game.advance_if_auto());
+        game.execute_helper(&step(Direction::Left), false);
+        assert!(!game.advance_if_auto());
+        assert_eq!(game.rank(), 2);
+    }
+
+    #[test]
+    fn goto_level() {
+        let (mut game, _receiver) = setup_game("original");
+        assert!(game.goto_level(1).is_ok());
+        assert!(game.goto_level(5).is_ok());
+        assert_eq!(game.rank(), 5);
+
+        assert!(game.goto_level(0).is_err());
+        assert!(game.goto_level(51).is_err());
+        assert_eq!(game.rank(), 5);
+    }
+
+    #[test]
+    fn macro_recording_events() {
+        let mut game = create_game();
+        let (sender, receiver) = channel();
+        game.subscribe_moves(sender);
+
+        let step = Command::Movement(Movement::Step {
+            direction: Direction::Up,
+        });
+        game.execute_helper(&Command::Macro(Macro::Record(3)), false);
+        game.execute_helper(&step, false);
+        game.execute_helper(&step, false);
+        game.execute_helper(&Command::Macro(Macro::Store), false);
+
+        let events: Vec<_> = receiver.try_iter().collect();
+        assert!(events.iter().all(|e| !e.is_error()));
+        match events.first() {
+            Some(Event::MacroRecordingStarted { slot: 3 }) => {}
+            e => panic!("unexpected event: {:?}", e),
+        }
+        assert!(events
+            .iter()
+            .any(|e| matches!(e, Event::MacroRecordingStopped { slot: 3, length: 2 })));
+    }
+
     fn create_game() -> Game {
         const LARGE_EMPTY_LEVEL: &str = r#"
 #########################################
@@ -573,18 +1126,114 @@ mod tests {
         const NAME: &str = "Test";
         let lvl = Level::parse(0, LARGE_EMPTY_LEVEL).unwrap();
         let collection = Collection::from_levels(NAME, &[lvl.clone()]);
+        let cancel = CancelHandle::default();
+        let mut current_level: CurrentLevel = lvl.into();
+        current_level.set_cancel_handle(cancel.clone());
         Game {
             rank: 1,
             name: "LARGE_EMPTY_LEVEL".into(),
             collection,
             macros: Macros::new(),
             state: CollectionState::new(""),
-            current_level: lvl.into(),
+            settings: CollectionSettings::default(),
+            current_level,
             listeners: Listeners::new(),
             receiver: None,
+            auto_advance: false,
+            last_snapshot: Instant::now(),
+            batch_moves: false,
+            wrap_collections: true,
+            cancel,
         }
     }
 
+    #[test]
+    fn resume_after_collection_changed() {
+        let lvl = Level::parse(0, "#####\n#@$.#\n#####").unwrap();
+        let mut solved: CurrentLevel = lvl.clone().into();
+        solved.try_move(Direction::Right).unwrap();
+        let finished = LevelState::new_solved(Solution::try_from(&solved).unwrap());
+
+        let mut game = Game {
+            collection: Collection::from_levels("Test", &[lvl.clone(), lvl.clone()]),
+            ..create_game()
+        };
+
+        // More levels have been finished than the collection now contains
+        let mut state = CollectionState::new("Test");
+        state.levels = vec![finished.clone(); 3];
+        game.resume_from(&mut state);
+        assert_eq!(state.number_of_levels(), 2);
+        assert_eq!(game.rank(), 2);
+
+        // Levels have been added since the state was saved
+        let mut state = CollectionState::new("Test");
+        state.levels = vec![finished];
+        game.resume_from(&mut state);
+        assert_eq!(game.rank(), 2);
+        assert_eq!(game.current_level().number_of_moves(), 0);
+    }
+
+    #[test]
+    fn collection_solved_event() {
+        let lvl = Level::parse(0, "#####\n#@$.#\n#####").unwrap();
+        let mut game = Game {
+            collection: Collection::from_levels("Tiny", std::slice::from_ref(&lvl)),
+            current_level: lvl.into(),
+            ..create_game()
+        };
+        let (sender, receiver) = channel();
+        game.subscribe_moves(sender);
+        let solved_events = |receiver: &Receiver<Event>| {
+            receiver
+                .try_iter()
+                .filter(|event| matches!(event, Event::CollectionSolved { name } if name == "Tiny"))
+                .count()
+        };
+
+        let step = Command::Movement(Movement::Step {
+            direction: Direction::Right,
+        });
+        game.execute_helper(&step, false);
+        assert!(game.state.collection_solved);
+        assert_eq!(solved_events(&receiver), 1);
+
+        // Only the first time
+        game.execute_helper(&Command::Nothing, false);
+        assert_eq!(solved_events(&receiver), 0);
+    }
+
+    #[test]
+    fn macro_with_reset() {
+        use crate::Direction::*;
+        let mut game = create_game();
+        let start = game.worker_position();
+        let step = |direction| Command::Movement(Movement::Step { direction });
+        let reset = Command::LevelManagement(LevelManagement::ResetLevel);
+        let record = |game: &mut Game| {
+            game.execute_helper(&Command::Macro(Macro::Record(0)), false);
+            game.execute_helper(&reset, false);
+            game.execute_helper(&step(Down), false);
+            game.execute_helper(&Command::Macro(Macro::Store), false);
+        };
+
+        // By default, resetting the level is not recorded
+        record(&mut game);
+        assert_eq!(game.macros.get(0).len(), 1);
+
+        game.set_macros_allow_management(true);
+        record(&mut game);
+        assert_eq!(game.macros.get(0).len(), 2);
+        assert_eq!(game.macros.to_string(0), "#d");
+
+        for _ in 0..3 {
+            game.execute_helper(&step(Right), false);
+        }
+        game.execute_helper(&Command::Macro(Macro::Execute(0)), false);
+        assert_eq!(game.current_level().number_of_moves(), 1);
+        assert_eq!(game.worker_position(), start.below());
+    }
+
     #[quickcheck]
     fn prop_move_undo(mut move_dirs: Vec<Direction>) -> bool {
         let mut game = create_game();
@@ -604,6 +1253,240 @@ mod tests {
             && current_lvl.number_of_moves() == lvl.number_of_moves()
     }
 
+    #[test]
+    fn pristine_after_undoing_everything() {
+        let mut game = create_game();
+        assert!(game.is_pristine());
+
+        for &direction in &[Direction::Down, Direction::Left] {
+            game.execute_helper(&Command::Movement(Movement::Step { direction }), false);
+        }
+        assert!(!game.is_pristine());
+
+        game.execute_helper(&Command::Movement(Movement::UndoAll), false);
+        assert!(game.is_pristine());
+    }
+
+    #[test]
+    fn cancel_long_walk() {
+        // The corridor is long enough for the walk to still be in progress when the other thread
+        // gets around to cancelling it.
+        const LENGTH: usize = 20_000;
+        let wall = "#".repeat(LENGTH + 5);
+        let s = format!("{}\n#.$@{}#\n{}", wall, " ".repeat(LENGTH), wall);
+        let lvl = Level::parse(0, &s).unwrap();
+        let mut game = Game::new(Collection::from_levels("cancel", &[lvl])).unwrap();
+        let target = Position::new(LENGTH + 3, 1);
+        let walk = Command::Movement(Movement::WalkToPosition { position: target });
+
+        // Requests made while nothing is moving do not affect later movements.
+        game.request_cancel();
+        game.execute_helper(&walk, false);
+        assert_eq!(game.worker_position(), target);
+
+        // The handle keeps working after the level has been reloaded.
+        game.execute_helper(
+            &Command::LevelManagement(LevelManagement::ResetLevel),
+            false,
+        );
+        let handle = game.cancel_handle();
+        let (sender, receiver) = channel();
+        game.subscribe_moves(sender);
+        let canceller = std::thread::spawn(move || {
+            let mut cancelled = false;
+            for event in receiver {
+                if let Event::MoveWorker { .. } = event {
+                    if !cancelled {
+                        handle.cancel();
+                        cancelled = true;
+                    }
+                }
+            }
+        });
+
+        game.execute_helper(&walk, false);
+        assert!(game.number_of_moves() > 0);
+        assert!(game.number_of_moves() < LENGTH);
+        assert_ne!(game.worker_position(), target);
+
+        drop(game);
+        canceller.join().unwrap();
+    }
+
+    #[test]
+    fn restore_redo_stack() {
+        let s = "#######\n\
+                 #@ $ .#\n\
+                 #     #\n\
+                 #######";
+        let lvl = Level::parse(0, s).unwrap();
+        let mut current_level: CurrentLevel = lvl.clone().into();
+        for &dir in &[
+            Direction::Right,
+            Direction::Right,
+            Direction::Down,
+            Direction::Right,
+        ] {
+            assert!(current_level.try_move(dir).is_ok());
+        }
+        current_level.undo();
+        current_level.undo();
+
+        let state = LevelState::new_unsolved(&current_level);
+        let bytes = serde_cbor::to_vec(&state).unwrap();
+        let state: LevelState = serde_cbor::from_slice(&bytes).unwrap();
+
+        let mut restored: CurrentLevel = lvl.into();
+        restore_moves(&mut restored, &state);
+        assert_eq!(restored.to_string(), current_level.to_string());
+        assert_eq!(restored.number_of_moves(), 2);
+        assert_eq!(restored.all_moves_to_string(), "rRdr");
+        assert!(restored.redo());
+        assert!(restored.redo());
+        assert!(!restored.redo());
+    }
+
+    #[test]
+    fn batch_moves() {
+        let s = "##########\n\
+                 #@      .#\n\
+                 #      $ #\n\
+                 ##########";
+        let lvl = Level::parse(0, s).unwrap();
+        let target = Position::new(6, 1);
+        let walk = Command::Movement(Movement::WalkToPosition { position: target });
+
+        for &batch in &[false, true] {
+            let collection = Collection::from_levels("batch", std::slice::from_ref(&lvl));
+            let mut game = Game::new(collection).unwrap();
+            game.set_batch_moves(batch);
+            let (sender, receiver) = channel();
+            game.subscribe_moves(sender);
+
+            game.execute_helper(&walk, false);
+            assert_eq!(game.worker_position(), target);
+            let events: Vec<_> = receiver
+                .try_iter()
+                .filter(|e| !matches!(e, Event::CellsRevealed { .. }))
+                .collect();
+            if batch {
+                assert_eq!(events.len(), 1);
+                match events[0] {
+                    Event::BatchMove {
+                        worker_id: 0,
+                        ref worker_path,
+                        ref crate_moves,
+                    } => {
+                        assert_eq!(worker_path.len(), 5);
+                        assert_eq!(worker_path.last(), Some(&target));
+                        assert!(crate_moves.is_empty());
+                    }
+                    ref e => panic!("unexpected event: {:?}", e),
+                }
+            } else {
+                assert_eq!(events.len(), 5);
+                assert!(events.iter().all(|e| matches!(e, Event::MoveWorker { .. })));
+            }
+        }
+    }
+
+    #[test]
+    fn count_commands() {
+        let s = "#########\n\
+                 #@     .#\n\
+                 #     $ #\n\
+                 #########";
+        let lvl = Level::parse(0, s).unwrap();
+        let mut game = Game::new(Collection::from_levels("commands", &[lvl])).unwrap();
+
+        let walk = Movement::WalkToPosition {
+            position: Position::new(6, 1),
+        };
+        game.execute_helper(&Command::Movement(walk), false);
+        assert_eq!(game.number_of_moves(), 5);
+        assert_eq!(game.current_level().number_of_commands(), 1);
+
+        let step = Movement::Step {
+            direction: Direction::Right,
+        };
+        game.execute_helper(&Command::Movement(step), false);
+        game.execute_helper(&Command::Movement(Movement::Undo), false);
+        assert_eq!(game.number_of_moves(), 5);
+        assert_eq!(game.current_level().number_of_commands(), 2);
+    }
+
+    #[test]
+    fn next_collection() {
+        let (mut game, _receiver) = setup_game("original");
+        assert!(game.next_collection().is_ok());
+        assert_eq!(game.collection.short_name(), "sasquatch_1");
+        assert_eq!(game.rank(), 1);
+
+        let last = crate::collection_names().unwrap().pop().unwrap();
+        game.set_collection(&last).unwrap();
+        game.set_wrap_collections(false);
+        assert!(game.next_collection().is_err());
+        assert_eq!(game.collection.short_name(), last);
+
+        game.set_wrap_collections(true);
+        assert!(game.next_collection().is_ok());
+        assert_eq!(
+            game.collection.short_name(),
+            crate::collection_names().unwrap()[0]
+        );
+    }
+
+    #[test]
+    fn keep_collection_on_error() {
+        let (mut game, _receiver) = setup_game("original");
+        assert!(game.goto_level(2).is_ok());
+        assert!(game.set_collection("no_such_collection").is_err());
+        assert_eq!(game.name, "original");
+        assert_eq!(game.collection.short_name(), "original");
+        assert_eq!(game.rank(), 2);
+    }
+
+    #[test]
+    fn snapshot_round_trip() {
+        use crate::Direction::*;
+        let (mut game, _receiver) = setup_game("original");
+        assert!(game.goto_level(3).is_ok());
+        let level = game.get_level(3);
+        game.set_current_level(&level, 3);
+        for &direction in &[Left, Up, Up, Left, Down, Right, Down, Left, Left] {
+            game.execute_helper(&Command::Movement(Movement::Step { direction }), false);
+        }
+        game.execute_helper(&Command::Movement(Movement::Undo), false);
+        assert!(game.number_of_moves() > 0);
+
+        let bytes = serde_cbor::to_vec(&game.snapshot()).unwrap();
+        let snapshot: GameSnapshot = serde_cbor::from_slice(&bytes).unwrap();
+        let restored = Game::restore(&snapshot).unwrap();
+        assert_eq!(GameSnapshot::load(), Some(snapshot));
+        assert_eq!(restored.rank(), 3);
+        assert_eq!(restored.number_of_moves(), game.number_of_moves());
+        assert_eq!(restored.worker_position(), game.worker_position());
+        assert_eq!(
+            restored.current_level().all_moves_to_string(),
+            game.current_level().all_moves_to_string()
+        );
+    }
+
+    #[test]
+    fn discard_snapshot_on_level_change() {
+        let levels = [
+            Level::parse(0, "#####\n#@$.#\n#####").unwrap(),
+            Level::parse(1, "#####\n#.$@#\n#####").unwrap(),
+        ];
+        let mut game = Game::new(Collection::from_levels("snapshot", &levels)).unwrap();
+        game.save_snapshot();
+        assert_eq!(GameSnapshot::load(), Some(game.snapshot()));
+        assert!(!crate::util::data_dir().join("snapshot.cbor").exists());
+
+        game.goto_level(2).unwrap();
+        assert_eq!(GameSnapshot::load(), None);
+    }
+
     #[test]
     fn test_undo() {
         let mut game = create_game();
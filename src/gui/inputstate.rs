@@ -1,6 +1,8 @@
 use glium::glutin::event::{ModifiersState, VirtualKeyCode};
 
-use crate::backend::{Command, Direction, LevelManagement, Macro, Movement, Position};
+use crate::backend::{
+    select_next_crate, Command, Direction, LevelManagement, Macro, Movement, Position,
+};
 
 #[derive(Default)]
 pub struct InputState {
@@ -8,11 +10,23 @@ pub struct InputState {
     pub cursor_position: [f64; 2],
 
     pub clicked_crate: Option<Position>,
+
+    /// The id of the crate moved by the arrow keys, if any. Pressing C cycles through all crates.
+    pub selected_crate: Option<usize>,
+
+    /// Should the cells reachable by the worker be highlighted?
+    pub show_reachable: bool,
 }
 
 impl InputState {
-    /// Handle key press events.
-    pub fn press_to_command(&mut self, key: VirtualKeyCode, modifiers: ModifiersState) -> Command {
+    /// Handle key press events. `crates` are the positions of all crates in the current level,
+    /// ordered by id.
+    pub fn press_to_command(
+        &mut self,
+        key: VirtualKeyCode,
+        modifiers: ModifiersState,
+        crates: &[Position],
+    ) -> Command {
         use self::Command::*;
         use self::LevelManagement::*;
         use self::Macro::*;
@@ -23,6 +37,21 @@ impl InputState {
             // Move
             Left | Right | Up | Down => {
                 let direction = key_to_direction(key);
+                if let Some(id) = self.selected_crate {
+                    // Push the selected crate instead of moving the worker. Its position is looked
+                    // up anew each time as the previous push may or may not have succeeded.
+                    let crate_pos = if let Some(&pos) = crates.get(id) {
+                        pos
+                    } else {
+                        warn!("The selected crate does not exist, press C to select one.");
+                        self.selected_crate = None;
+                        return Nothing;
+                    };
+                    return Movement(NudgeCrate {
+                        crate_pos,
+                        direction,
+                    });
+                }
                 return match (modifiers.ctrl(), modifiers.shift()) {
                     (false, false) => Movement(Step { direction }),
                     (false, true) => Movement(WalkTillObstacle { direction }),
@@ -34,8 +63,11 @@ impl InputState {
             // Undo and redo
             Z if !modifiers.ctrl() => {}
             U if modifiers.ctrl() => {}
-            U | Z if modifiers.shift()=> return Movement(Redo),
+            U | Z if modifiers.shift() => return Movement(Redo),
             U | Z => return Movement(Undo),
+            Home => return Movement(UndoAll),
+            End => return Movement(RedoAll),
+            Back => return Movement(UndoToLastPush),
 
             // Record or execute macro
             F1 | F2 | F3 | F4 | F5 | F6 | F7 | F8 | F9 | F10 | F11 | F12 => {
@@ -60,6 +92,12 @@ impl InputState {
             S if modifiers.ctrl() => return LevelManagement(Save),
             Escape => return LevelManagement(ResetLevel),
 
+            // Toggle highlighting the worker's reachable region
+            H => self.show_reachable = !self.show_reachable,
+
+            // Select the next crate for moving it using the arrow keys
+            C => self.selected_crate = select_next_crate(self.selected_crate, crates.len()),
+
             LAlt | LControl | LShift | LWin | RAlt | RControl | RShift | RWin => {}
             _ => error!("Unknown key: {:?}", key),
         }
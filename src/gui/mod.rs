@@ -11,8 +11,8 @@ use std::{
 use glium::{
     self,
     backend::glutin::Display,
-    glutin::{self, dpi},
     glutin::event::{ModifiersState, MouseButton},
+    glutin::{self, dpi},
     index::{NoIndices, PrimitiveType},
     texture::Texture2d,
     Program, Surface,
@@ -32,6 +32,12 @@ const NO_INDICES: NoIndices = NoIndices(PrimitiveType::TrianglesList);
 const CULLING: glium::BackfaceCullingMode =
     glium::draw_parameters::BackfaceCullingMode::CullCounterClockwise;
 
+/// The colour reachable cells are tinted with if `Gui::show_reachable` is set.
+const REACHABLE_TINT: [f32; 4] = [0.3, 0.6, 1.0, 0.35];
+
+/// The colour the crate selected for moving with the arrow keys is tinted with.
+const SELECTED_TINT: [f32; 4] = [1.0, 0.8, 0.1, 0.45];
+
 const IDENTITY: [[f32; 4]; 4] = {
     [
         [1.0, 0.0, 0.0, 0.0],
@@ -77,12 +83,18 @@ pub struct Gui {
     /// The size of the window in pixels as `[width, height]`.
     pub window_size: [u32; 2],
 
-    /// Tile textures, i.e. wall, worker, crate, etc.
-    pub textures: Textures,
+    /// Decides which textures to draw walls, workers, crates, etc. with.
+    pub renderer: Box<dyn TileRenderer>,
 
     /// Pre-rendered static part of the current level, i.e. walls, floors and goals.
     pub background_texture: Option<Texture2d>,
 
+    /// Highlight the cells the worker can reach without pushing crates.
+    pub show_reachable: bool,
+
+    /// The id of the crate selected for moving with the arrow keys, which is highlighted.
+    pub selected_crate: Option<usize>,
+
     pub worker: Sprite,
     pub crates: Vec<Sprite>,
 
@@ -107,7 +119,7 @@ impl Gui {
             .window()
             .set_cursor_icon(glutin::window::CursorIcon::Default);
 
-        let textures = Textures::new(&display);
+        let renderer = Box::new(DefaultTileRenderer::new(&display));
         // let font_data = Rc::new(FontData::new(
         //     &display,
         //     ASSETS.join("FiraSans-Regular.ttf"),
@@ -159,8 +171,10 @@ impl Gui {
             matrix: IDENTITY,
             program,
             window_size: [800, 600],
-            textures,
+            renderer,
             background_texture: None,
+            show_reachable: false,
+            selected_crate: None,
 
             worker,
             crates: vec![],
@@ -202,6 +216,23 @@ impl Gui {
         self.window_aspect_ratio() * self.columns as f32 / self.rows as f32
     }
 
+    /// Turn highlighting the worker's reachable region on or off.
+    pub fn set_show_reachable(&mut self, show_reachable: bool) {
+        if self.show_reachable != show_reachable {
+            self.show_reachable = show_reachable;
+            self.background_texture = None;
+            self.need_to_redraw = true;
+        }
+    }
+
+    /// Change which crate is highlighted as selected.
+    pub fn set_selected_crate(&mut self, selected_crate: Option<usize>) {
+        if self.selected_crate != selected_crate {
+            self.selected_crate = selected_crate;
+            self.need_to_redraw = true;
+        }
+    }
+
     /// Has the current level been solved, i.e. should the end-of-level overlay be rendered?
     pub fn level_solved(&self) -> bool {
         match self.state {
@@ -234,20 +265,15 @@ impl Gui {
                     Command::Nothing
                 }
             } else {
-                let worker = self.worker_position;
-                let same_row_or_column = target.x == worker.x || target.y == worker.y;
                 let can_move_crate = mouse_button == MouseButton::Right;
 
-                match (same_row_or_column, can_move_crate) {
-                    (true, true) => Command::Movement(Movement::PushTowards { position: target }),
-                    (true, false) => Command::Movement(Movement::WalkTowards { position: target }),
-                    (false, false) => {
-                        Command::Movement(Movement::WalkToPosition { position: target })
-                    }
-                    (false, true) => {
-                        warn!("Cannot push crate to a different row and column.");
-                        Command::Nothing
-                    }
+                if let Some(movement) =
+                    Movement::from_click(self.worker_position, target, can_move_crate)
+                {
+                    Command::Movement(movement)
+                } else {
+                    warn!("Cannot push crate to a different row and column.");
+                    Command::Nothing
                 }
             }
         } else {
@@ -336,7 +362,12 @@ impl Gui {
             let mut surface = target.as_surface();
 
             // Render each of the (square) tiles
-            for &background in &[Background::Floor, Background::Goal, Background::Wall] {
+            for &background in &[
+                Background::Floor,
+                Background::Ice,
+                Background::Goal,
+                Background::Wall,
+            ] {
                 let vertices = generate_vertices_for(level, background);
                 let vb = glium::VertexBuffer::new(&self.display, &vertices).unwrap();
 
@@ -347,20 +378,45 @@ impl Gui {
                     .draw(&vb, &NO_INDICES, program, &uniforms, &self.params)
                     .unwrap();
             }
+
+            if self.show_reachable {
+                self.draw_reachable_cells(&mut surface);
+            }
         }
 
         self.background_texture = Some(target);
     }
 
-    fn background_to_texture(&self, background: Background) -> &Texture2d {
-        match background {
-            Background::Empty => unreachable!(),
-            Background::Floor => &self.textures.floor,
-            Background::Goal => &self.textures.goal,
-            Background::Wall => &self.textures.wall,
+    /// Draw the cells reachable by the worker once more, tinted with `REACHABLE_TINT`.
+    fn draw_reachable_cells<S: Surface>(&self, surface: &mut S) {
+        let program =
+            Program::from_source(&self.display, VERTEX_SHADER, TINT_SHADER, None).unwrap();
+        let level = self.current_level();
+        let columns = level.columns() as u32;
+        let rows = level.rows() as u32;
+        let reachable = level.reachable_cells();
+
+        for &background in &[Background::Floor, Background::Ice, Background::Goal] {
+            let vertices: Vec<_> = reachable
+                .iter()
+                .filter(|&&pos| level.background(pos) == background)
+                .flat_map(|&pos| texture::quad(pos, columns, rows))
+                .collect();
+            let vb = glium::VertexBuffer::new(&self.display, &vertices).unwrap();
+
+            let texture = self.background_to_texture(background);
+            let uniforms = uniform! {tex: texture, matrix: self.matrix, tint: REACHABLE_TINT};
+
+            surface
+                .draw(&vb, NO_INDICES, &program, &uniforms, &self.params)
+                .unwrap();
         }
     }
 
+    fn background_to_texture(&self, background: Background) -> &Texture2d {
+        self.renderer.texture_for(background, Foreground::None)
+    }
+
     fn generate_empty_background_texture(&self) -> Texture2d {
         let width = self.window_size[0];
         let height = self.window_size[1];
@@ -373,15 +429,14 @@ impl Gui {
     fn update_sprites(&mut self) {
         self.worker = Sprite::new(self.worker_position, texture::TileKind::Worker);
         self.worker.set_direction(self.worker_direction);
+        let level = self.current_level();
         self.crates = self
             .game
             .crate_positions()
             .iter()
-            .map(|&pos| Sprite::new(pos, texture::TileKind::Crate))
+            .map(|&pos| Sprite::new(pos, TileKind::crate_on(level.background(pos))))
             .collect();
         // TODO simplify hashmap -> iter -> vec -> iter -> vec -> iter -> vec
-
-        self.background_texture = None;
     }
 
     /// Given a vector of vertices describing a list of quads, draw them onto `target`.
@@ -406,7 +461,7 @@ impl Gui {
             target,
             texture::full_screen(),
             // The texture is ignored by the given fragment shader, so we can take any here
-            &self.textures.worker, // FIXME find a cleaner solution
+            self.background_to_texture(Background::Floor), // FIXME find a cleaner solution
             &program,
         )
         .unwrap();
@@ -426,10 +481,11 @@ impl Gui {
 
         // let stats_text = format!(
         //     "You have finished the level {} using {} moves, \
-        //      {} of which moved a crate.",
+        //      {} of which moved a crate, and {} undos.",
         //     self.rank,
         //     self.game.number_of_moves(),
-        //     self.game.number_of_pushes()
+        //     self.game.number_of_pushes(),
+        //     self.current_level().number_of_undos()
         // );
 
         // font_data.draw(
@@ -484,14 +540,48 @@ impl Gui {
         let mut draw = |vs, tex| self.draw_quads(target, vs, tex, &self.program).unwrap();
 
         // Draw the crates
-        let mut vertices = vec![];
-        for sprite in &self.crates {
-            vertices.extend(sprite.quad(columns, rows));
+        for &(tile_kind, texture) in &[
+            (
+                TileKind::Crate,
+                self.renderer
+                    .texture_for(Background::Floor, Foreground::Crate),
+            ),
+            (
+                TileKind::CrateOnGoal,
+                self.renderer
+                    .texture_for(Background::Goal, Foreground::Crate),
+            ),
+        ] {
+            let mut vertices = vec![];
+            for sprite in self.crates.iter().filter(|s| s.tile_kind() == tile_kind) {
+                vertices.extend(sprite.quad(columns, rows));
+            }
+            draw(vertices, texture);
         }
-        draw(vertices, &self.textures.crate_);
 
         // Draw the worker
-        draw(self.worker.quad(columns, rows), &self.textures.worker);
+        let worker = self
+            .renderer
+            .texture_for(Background::Floor, Foreground::Worker);
+        draw(self.worker.quad(columns, rows), worker);
+
+        // Draw the selected crate once more, tinted with `SELECTED_TINT`
+        if let Some(sprite) = self.selected_crate.and_then(|id| self.crates.get(id)) {
+            let program =
+                Program::from_source(&self.display, VERTEX_SHADER, TINT_SHADER, None).unwrap();
+            let background = if let TileKind::CrateOnGoal = sprite.tile_kind() {
+                Background::Goal
+            } else {
+                Background::Floor
+            };
+            let texture = self.renderer.texture_for(background, Foreground::Crate);
+            let vertices = sprite.quad(columns, rows);
+            let vb = glium::VertexBuffer::new(&self.display, &vertices).unwrap();
+            let uniforms = uniform! {tex: texture, matrix: self.matrix, tint: SELECTED_TINT};
+            target
+                .draw(&vb, NO_INDICES, &program, &uniforms, &self.params)
+                .unwrap();
+        }
     }
 
     fn statistics_text(&self) -> String {
@@ -574,6 +664,7 @@ impl Gui {
                 if !self.worker.is_animated() {
                     self.background_texture = None;
                     self.state = State::LevelSolved;
+                    self.game.advance_if_auto();
                 }
             }
             State::LevelSolved => self.render_end_of_level(),
@@ -636,12 +727,28 @@ impl Gui {
         }
     }
 
+    fn move_crate_sprite(&mut self, id: usize, to: backend::Position) {
+        let tile_kind = TileKind::crate_on(self.current_level().background(to));
+        self.crates[id].move_to(to);
+        self.crates[id].set_tile_kind(tile_kind);
+        if self.show_reachable {
+            // Moving a crate may change which cells the worker can reach
+            self.background_texture = None;
+        }
+    }
+
     fn handle_response(&mut self, event: crate::backend::Event) -> bool {
         use crate::backend::Event::*;
         match event {
             LevelFinished(resp) if !self.level_solved() => {
                 self.state = State::FinishAnimation;
                 log_update_response(resp);
+                info!(
+                    "Solved using {} moves and {} pushes with {} undos.",
+                    self.game.number_of_moves(),
+                    self.game.number_of_pushes(),
+                    self.current_level().number_of_undos()
+                );
                 self.need_to_redraw = true;
             }
             LevelFinished(_) => {}
@@ -668,9 +775,14 @@ impl Gui {
 
                 self.state = State::Level;
                 self.update_sprites();
+                // The static tiles only change when a level is (re)loaded, so this is the only
+                // place apart from resizing the window where the cached background has to go.
+                self.background_texture = None;
                 self.need_to_redraw = true;
             }
+            // Only the first worker is drawn so far.
             MoveWorker {
+                worker_id: 0,
                 from: _from,
                 to,
                 direction,
@@ -681,14 +793,32 @@ impl Gui {
                 return true;
             }
             MoveCrate { id, to, .. } => {
-                self.crates[id].move_to(to);
+                self.move_crate_sprite(id, to);
+                self.need_to_redraw = true;
+            }
+            BatchMove {
+                worker_id,
+                worker_path,
+                crate_moves,
+            } => {
+                for (id, _from, to) in crate_moves {
+                    self.move_crate_sprite(id, to);
+                }
+                if let (0, Some(&to)) = (worker_id, worker_path.last()) {
+                    self.worker.move_to(to);
+                    self.worker.set_direction(self.game.worker_direction());
+                }
                 self.need_to_redraw = true;
+                return true;
             }
 
             EndOfCollection => {
                 self.is_last_level = true;
                 self.need_to_redraw = true;
             }
+            CollectionSolved { name } => {
+                info!("You have solved every level of {}. Congratulations!", name)
+            }
             _ => {}
         }
 
@@ -53,6 +53,15 @@ impl Sprite {
         // TODO What if self.animation.get() != None?
     }
 
+    pub fn tile_kind(&self) -> TileKind {
+        self.tile_kind
+    }
+
+    /// Change what sort of tile is shown, e.g. when a crate is pushed onto a goal.
+    pub fn set_tile_kind(&mut self, tile_kind: TileKind) {
+        self.tile_kind = tile_kind;
+    }
+
     /// Turn the sprite in a specific direction.
     pub fn set_direction(&mut self, dir: Direction) {
         self.direction = dir;
@@ -1,9 +1,10 @@
 use glium::{self, backend::Facade, texture::Texture2d};
 
-use crate::backend::{Direction, Position, ASSETS};
+use crate::backend::{Background, Direction, Foreground, Position, ASSETS};
 
 pub struct Textures {
     pub crate_: Texture2d,
+    pub crate_on_goal: Texture2d,
     pub floor: Texture2d,
     pub goal: Texture2d,
     pub wall: Texture2d,
@@ -14,6 +15,7 @@ impl Textures {
     /// Load all textures.
     pub fn new(factory: &dyn Facade) -> Self {
         let crate_ = load(factory, "crate");
+        let crate_on_goal = load(factory, "crate_on_goal");
         let floor = load(factory, "floor");
         let goal = load(factory, "goal");
         let wall = load(factory, "wall");
@@ -21,6 +23,7 @@ impl Textures {
 
         Textures {
             crate_,
+            crate_on_goal,
             floor,
             goal,
             wall,
@@ -29,6 +32,43 @@ impl Textures {
     }
 }
 
+/// Decides which texture a tile is drawn with. Implement this to replace the built-in tile set.
+pub trait TileRenderer {
+    /// The texture for a cell with the given contents. If `fg` is not `Foreground::None`, only
+    /// the foreground is drawn using the returned texture, on top of the background.
+    fn texture_for(&self, bg: Background, fg: Foreground) -> &Texture2d;
+}
+
+/// Draws tiles using the images in the assets directory.
+pub struct DefaultTileRenderer {
+    textures: Textures,
+}
+
+impl DefaultTileRenderer {
+    pub fn new(factory: &dyn Facade) -> Self {
+        DefaultTileRenderer {
+            textures: Textures::new(factory),
+        }
+    }
+}
+
+impl TileRenderer for DefaultTileRenderer {
+    fn texture_for(&self, bg: Background, fg: Foreground) -> &Texture2d {
+        match (bg, fg) {
+            (_, Foreground::Worker) | (_, Foreground::OtherWorker) => &self.textures.worker,
+            (Background::Goal, Foreground::Crate) => &self.textures.crate_on_goal,
+            (_, Foreground::Crate) => &self.textures.crate_,
+            (Background::Empty, Foreground::None) => unreachable!(),
+            // TODO There is no separate texture for ice yet.
+            (Background::Floor, Foreground::None) | (Background::Ice, Foreground::None) => {
+                &self.textures.floor
+            }
+            (Background::Goal, Foreground::None) => &self.textures.goal,
+            (Background::Wall, Foreground::None) => &self.textures.wall,
+        }
+    }
+}
+
 /// Load an image from the assets directory and turn it into a `Texture2d`.
 pub fn load(display: &dyn Facade, name: &str) -> Texture2d {
     let mut path = ASSETS.join("images");
@@ -79,6 +119,24 @@ void main() {
 }
 "#;
 
+/// Render texture on triangles, blending it with the colour given by the `tint` uniform. The
+/// alpha channel of `tint` determines how strongly the colour is blended in: 0.0 leaves the
+/// texture unchanged while 1.0 replaces it completely.
+pub const TINT_SHADER: &str = r#"
+#version 140
+
+in vec2 v_tex_coords;
+out vec4 color;
+
+uniform sampler2D tex;
+uniform vec4 tint;
+
+void main() {
+    vec4 tex_color = texture(tex, v_tex_coords);
+    color = vec4(mix(tex_color.rgb, tint.rgb, tint.a), tex_color.a);
+}
+"#;
+
 /// Darken the screen
 pub const DARKEN_SHADER: &str = r#"
 #version 140
@@ -91,12 +149,24 @@ void main() {
 }
 "#;
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum TileKind {
     Crate,
+    CrateOnGoal,
     Worker,
 }
 
+impl TileKind {
+    /// Which kind of tile should be used for a crate on top of the given background?
+    pub fn crate_on(background: Background) -> Self {
+        if background == Background::Goal {
+            TileKind::CrateOnGoal
+        } else {
+            TileKind::Crate
+        }
+    }
+}
+
 /// All tiles face left by default, so the worker has to turned by 90 degrees (clockwise) to face
 /// up instead of left, etc.
 fn direction_to_index(dir: Direction) -> usize {
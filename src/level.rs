@@ -2,17 +2,21 @@ pub mod builder;
 
 use std::{collections::HashMap, fmt};
 
-use crate::level::builder::{Foreground, LevelBuilder};
+pub use crate::level::builder::Foreground;
+use crate::level::builder::LevelBuilder;
 use crate::position::*;
 use crate::util::*;
 
 /// Static part of a cell.
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum Background {
     Empty,
     Wall,
     Floor,
     Goal,
+
+    /// Floor on which crates keep sliding once pushed.
+    Ice,
 }
 
 impl Background {
@@ -24,6 +28,19 @@ impl Background {
     }
 }
 
+/// Everything in a single cell of a level.
+pub type CellContents = (Background, Foreground);
+
+/// Ways of rotating or mirroring a level. Rotations are clockwise.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Transform {
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    FlipHorizontal,
+    FlipVertical,
+}
+
 #[derive(Debug, Clone)]
 pub struct Level {
     pub columns: usize,
@@ -37,22 +54,245 @@ pub struct Level {
 
     /// Where the worker is at the moment
     pub worker_position: Position,
+
+    /// Where the additional workers are in levels with more than one worker
+    pub other_workers: Vec<Position>,
+
+    /// The number of goals without a crate on them, which equals the number of crates not
+    /// on a goal
+    pub empty_goals: usize,
+
+    /// The level's title, if the collection provides one
+    pub title: Option<String>,
+}
+
+/// Optional checks to perform when parsing a level. By default, none of them are enabled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// Reject levels that are not enclosed by walls and do not guess which spaces are floor.
+    pub strict: bool,
+
+    /// Reject levels without any goals, e.g. the decorative "levels" some collections contain.
+    pub require_goals: bool,
 }
 
 /// Parse level and some basic utility functions. None of these change an existing `Level`.
 impl Level {
     /// Parse the ASCII representation of a level.
     pub fn parse(num: usize, string: &str) -> Result<Level, SokobanError> {
-        let builder = LevelBuilder::new(num + 1, string)?;
-        Ok(builder.build())
+        Level::parse_with_options(num, string, ParseOptions::default())
+    }
+
+    /// Parse the ASCII representation of a level, rejecting levels that are not enclosed by
+    /// walls. Unlike `parse`, this does not try to guess which spaces are floor, so empty cells
+    /// inside the level stay empty. Floor has to be marked using `-` or `_`.
+    pub fn parse_strict(num: usize, string: &str) -> Result<Level, SokobanError> {
+        let options = ParseOptions {
+            strict: true,
+            ..ParseOptions::default()
+        };
+        Level::parse_with_options(num, string, options)
+    }
+
+    /// Parse the ASCII representation of a level, performing the checks selected in `options`.
+    pub fn parse_with_options(
+        num: usize,
+        string: &str,
+        options: ParseOptions,
+    ) -> Result<Level, SokobanError> {
+        let builder = if options.strict {
+            LevelBuilder::new_strict(num + 1, string)?
+        } else {
+            LevelBuilder::new(num + 1, string)?
+        };
+        builder.build(options)
+    }
+
+    /// The number of crates in the level.
+    pub fn number_of_crates(&self) -> usize {
+        self.crates.len()
+    }
+
+    /// The number of goals in the level, whether or not there is a crate on them.
+    pub fn number_of_goals(&self) -> usize {
+        self.background
+            .iter()
+            .filter(|&&bg| bg == Background::Goal)
+            .count()
     }
 
     /// Is there a crate at the given position?
     fn is_crate(&self, pos: Position) -> bool {
         self.crates.get(&pos).is_some()
     }
+
+    /// What is on top of the background at the given position?
+    fn foreground(&self, pos: Position) -> Foreground {
+        if self.worker_position == pos {
+            Foreground::Worker
+        } else if self.other_workers.contains(&pos) {
+            Foreground::OtherWorker
+        } else if self.is_crate(pos) {
+            Foreground::Crate
+        } else {
+            Foreground::None
+        }
+    }
+
+    /// List all cells whose contents differ between `self` and `other`, in reading order, with
+    /// the contents in `self` followed by those in `other`. Both levels must have the same size.
+    pub fn diff(
+        &self,
+        other: &Level,
+    ) -> Result<Vec<(Position, CellContents, CellContents)>, SokobanError> {
+        if self.columns != other.columns || self.rows != other.rows {
+            return Err(SokobanError::SizeMismatch(
+                self.columns,
+                self.rows,
+                other.columns,
+                other.rows,
+            ));
+        }
+
+        let mut result = vec![];
+        for (i, (&bg, &other_bg)) in self.background.iter().zip(&other.background).enumerate() {
+            let pos = Position::from_index(i, self.columns);
+            let cell = (bg, self.foreground(pos));
+            let other_cell = (other_bg, other.foreground(pos));
+            if cell != other_cell {
+                result.push((pos, cell, other_cell));
+            }
+        }
+        Ok(result)
+    }
+
+    /// Create a rotated or mirrored copy of the level.
+    pub fn transform(&self, t: Transform) -> Level {
+        use self::Transform::*;
+
+        let (columns, rows) = match t {
+            Rotate90 | Rotate270 => (self.rows, self.columns),
+            Rotate180 | FlipHorizontal | FlipVertical => (self.columns, self.rows),
+        };
+        let max_x = self.columns as isize - 1;
+        let max_y = self.rows as isize - 1;
+        let map = |pos: Position| {
+            let Position { x, y } = pos;
+            let (x, y) = match t {
+                Rotate90 => (max_y - y, x),
+                Rotate180 => (max_x - x, max_y - y),
+                Rotate270 => (y, max_x - x),
+                FlipHorizontal => (max_x - x, y),
+                FlipVertical => (x, max_y - y),
+            };
+            Position { x, y }
+        };
+
+        let mut background = vec![Background::Empty; columns * rows];
+        for (i, &bg) in self.background.iter().enumerate() {
+            let pos = map(Position::from_index(i, self.columns));
+            background[pos.to_index(columns)] = bg;
+        }
+
+        Level {
+            columns,
+            rows,
+            background,
+            crates: self
+                .crates
+                .iter()
+                .map(|(&pos, &id)| (map(pos), id))
+                .collect(),
+            worker_position: map(self.worker_position),
+            other_workers: self.other_workers.iter().map(|&pos| map(pos)).collect(),
+            empty_goals: self.empty_goals,
+            title: self.title.clone(),
+        }
+    }
+
+    /// A rough guess of how hard the level is, based only on its geometry. Crates count the
+    /// most, followed by one cell wide corridors, goals and the size of the interior.
+    pub fn estimated_difficulty(&self) -> u32 {
+        let columns = self.columns as isize;
+        let rows = self.rows as isize;
+        let is_wall = |x: isize, y: isize| {
+            x < 0
+                || y < 0
+                || x >= columns
+                || y >= rows
+                || self.background[(y * columns + x) as usize].is_wall()
+        };
+
+        let mut interior = 0;
+        let mut goals = 0;
+        let mut corridors = 0;
+        for (i, &bg) in self.background.iter().enumerate() {
+            match bg {
+                Background::Floor | Background::Ice => {}
+                Background::Goal => goals += 1,
+                Background::Empty | Background::Wall => continue,
+            }
+            interior += 1;
+
+            let Position { x, y } = Position::from_index(i, self.columns);
+            if is_wall(x - 1, y) && is_wall(x + 1, y) || is_wall(x, y - 1) && is_wall(x, y + 1) {
+                corridors += 1;
+            }
+        }
+
+        10 * self.crates.len() as u32 + 3 * corridors + 2 * goals + interior / 4
+    }
+
+    /// Create a copy of the level without the rows and columns that are entirely empty.
+    pub fn cropped(&self) -> Level {
+        let columns = self.columns;
+        let non_empty = || {
+            self.background
+                .iter()
+                .enumerate()
+                .filter(|&(_, &bg)| bg != Background::Empty)
+                .map(|(i, _)| (i % columns, i / columns))
+        };
+        let min_x = non_empty().map(|(x, _)| x).min().unwrap_or(0);
+        let max_x = non_empty().map(|(x, _)| x).max().unwrap_or(0);
+        let min_y = non_empty().map(|(_, y)| y).min().unwrap_or(0);
+        let max_y = non_empty().map(|(_, y)| y).max().unwrap_or(0);
+
+        let new_columns = max_x - min_x + 1;
+        let new_rows = max_y - min_y + 1;
+        let mut background = Vec::with_capacity(new_columns * new_rows);
+        for y in min_y..=max_y {
+            let start = y * columns + min_x;
+            background.extend_from_slice(&self.background[start..start + new_columns]);
+        }
+
+        let offset = Position::new(min_x, min_y);
+        let shift = |pos: Position| Position {
+            x: pos.x - offset.x,
+            y: pos.y - offset.y,
+        };
+
+        Level {
+            columns: new_columns,
+            rows: new_rows,
+            background,
+            crates: self
+                .crates
+                .iter()
+                .map(|(&pos, &id)| (shift(pos), id))
+                .collect(),
+            worker_position: shift(self.worker_position),
+            other_workers: self.other_workers.iter().map(|&pos| shift(pos)).collect(),
+            empty_goals: self.empty_goals,
+            title: self.title.clone(),
+        }
+    }
 }
 
+/// The character representing a cell in the usual ASCII format. There are no characters for
+/// crates or workers on ice, so they are written as if they were on floor and the ice beneath
+/// them is lost when the output is parsed again. Likewise, floor is written as a space just like
+/// empty cells, so `Level::parse_strict` cannot tell them apart in the output.
 fn cell_to_char(background: Background, foreground: Foreground) -> char {
     match (background, foreground) {
         (Background::Wall, Foreground::None) => '#',
@@ -62,6 +302,13 @@ fn cell_to_char(background: Background, foreground: Foreground) -> char {
         (Background::Goal, Foreground::None) => '.',
         (Background::Goal, Foreground::Crate) => '*',
         (Background::Goal, Foreground::Worker) => '+',
+        (Background::Floor, Foreground::OtherWorker) => '&',
+        (Background::Goal, Foreground::OtherWorker) => '%',
+        (Background::Ice, Foreground::None) => '~',
+        // There are no separate characters for crates or workers on ice.
+        (Background::Ice, Foreground::Crate) => '$',
+        (Background::Ice, Foreground::Worker) => '@',
+        (Background::Ice, Foreground::OtherWorker) => '&',
         _ => panic!(
             "Invalid combination: {:?} on top of {:?}",
             foreground, background
@@ -78,14 +325,7 @@ impl fmt::Display for Level {
             }
             for j in 0..columns {
                 let background = self.background[j + i * self.columns];
-                let pos = Position::new(j, i);
-                let foreground = if self.worker_position == pos {
-                    Foreground::Worker
-                } else if self.is_crate(pos) {
-                    Foreground::Crate
-                } else {
-                    Foreground::None
-                };
+                let foreground = self.foreground(Position::new(j, i));
                 let cell = cell_to_char(background, foreground);
                 write!(f, "{}", cell)?;
             }
@@ -96,8 +336,13 @@ impl fmt::Display for Level {
 
 #[cfg(test)]
 impl Level {
+    /// The extent of the level's grid.
+    pub fn bounds(&self) -> Bounds {
+        Bounds::new(self.columns, self.rows)
+    }
+
     fn index(&self, pos: Position) -> usize {
-        pos.x as usize + pos.y as usize * self.columns
+        self.bounds().index(pos)
     }
 
     fn background(&self, pos: Position) -> &Background {
@@ -105,7 +350,7 @@ impl Level {
     }
 
     fn in_bounds(&self, pos: Position) -> bool {
-        pos.x >= 0 && pos.y >= 0 && pos.x < self.columns as isize && pos.y < self.rows as isize
+        self.bounds().contains(pos)
     }
 
     /// The cell at the given position is neither empty, nor does it contain a wall.
@@ -117,7 +362,7 @@ impl Level {
         }
 
         match *self.background(pos) {
-            Floor | Goal => true,
+            Floor | Goal | Ice => true,
             _ => false,
         }
     }
@@ -138,6 +383,174 @@ mod test {
         );
     }
 
+    #[test]
+    fn enclosed() {
+        let s = "#####\n\
+                 #@$.#\n\
+                 #####";
+        assert!(Level::parse_strict(0, s).is_ok());
+
+        let s = "#####\n\
+                 #@$. \n\
+                 #####";
+        let res = Level::parse_strict(0, s);
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "Level #1 is not enclosed by walls"
+        );
+    }
+
+    #[test]
+    fn no_goals() {
+        let s = "#####\n\
+                 #@--#\n\
+                 #####";
+        assert_eq!(Level::parse(0, s).unwrap().number_of_goals(), 0);
+        assert!(Level::parse_strict(0, s).is_ok());
+
+        let options = ParseOptions {
+            require_goals: true,
+            ..ParseOptions::default()
+        };
+        match Level::parse_with_options(0, s, options) {
+            Err(SokobanError::NoGoals(1)) => {}
+            res => panic!("unexpected result: {:?}", res),
+        }
+        let s = "#####\n\
+                 #@$.#\n\
+                 #####";
+        assert!(Level::parse_with_options(0, s, options).is_ok());
+    }
+
+    #[test]
+    fn diff() {
+        let s = "#####\n\
+                 #@$.#\n\
+                 #####";
+        let lvl = Level::parse(0, s).unwrap();
+        let mut other = lvl.clone();
+        other.background[2] = Background::Floor;
+
+        let diff = lvl.diff(&other).unwrap();
+        assert_eq!(diff.len(), 1);
+        assert_eq!(diff[0].0, Position::new(2, 0));
+        assert_eq!(diff[0].1, (Background::Wall, Foreground::None));
+        assert_eq!(diff[0].2, (Background::Floor, Foreground::None));
+
+        assert!(lvl.diff(&lvl).unwrap().is_empty());
+        assert!(lvl.diff(&lvl.transform(Transform::Rotate90)).is_err());
+    }
+
+    #[test]
+    fn too_large() {
+        use super::builder::MAX_CELLS;
+        let s = format!("#####\n#@$.#\n{}#", " ".repeat(MAX_CELLS / 3));
+        let res = Level::parse(0, &s);
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            format!("Level #1 is too large ({} cells)", 3 * (MAX_CELLS / 3 + 1))
+        );
+    }
+
+    #[test]
+    fn strict_keeps_empty_cells() {
+        let s = "######\n\
+                 #@$-.#\n\
+                 #- --#\n\
+                 ######";
+        let hole = Position::new(2, 2);
+
+        let lvl = Level::parse(0, s).unwrap();
+        assert_eq!(*lvl.background(hole), Background::Floor);
+        assert_eq!(*lvl.background(Position::new(3, 1)), Background::Floor);
+
+        let lvl = Level::parse_strict(0, s).unwrap();
+        assert_eq!(*lvl.background(hole), Background::Empty);
+        assert_eq!(*lvl.background(Position::new(1, 2)), Background::Floor);
+
+        // Floor is printed as a space, so it is empty after parsing the output strictly
+        let printed = lvl.to_string();
+        assert_eq!(printed, "######\n#@$ .#\n#    #\n######");
+        let reparsed = Level::parse_strict(0, &printed).unwrap();
+        assert_eq!(*reparsed.background(Position::new(1, 2)), Background::Empty);
+    }
+
+    #[test]
+    fn alternate_floor_glyphs() {
+        let spaces = "#######\n\
+                      #@ $ .#\n\
+                      #  #  #\n\
+                      #######";
+        let glyphs = "#######\n\
+                      #@-$_.#\n\
+                      #_-#-_#\n\
+                      #######";
+
+        let expected = Level::parse(0, spaces).unwrap().to_string();
+        assert_eq!(Level::parse(0, glyphs).unwrap().to_string(), expected);
+        assert_eq!(
+            Level::parse_strict(0, glyphs).unwrap().to_string(),
+            expected
+        );
+    }
+
+    #[test]
+    fn count_crates_and_goals() {
+        let lvl = Level::parse(
+            0,
+            "#######\n\
+             #@ $ .#\n\
+             # $*. #\n\
+             #######",
+        )
+        .unwrap();
+        assert_eq!(lvl.number_of_crates(), 3);
+        assert_eq!(lvl.number_of_goals(), 3);
+    }
+
+    #[test]
+    fn count_empty_goals() {
+        let lvl = Level::parse(
+            0,
+            "########\n\
+             #@$.*  #\n\
+             # $ *. #\n\
+             ########",
+        )
+        .unwrap();
+        assert_eq!(lvl.empty_goals, 2);
+        assert!(!crate::CurrentLevel::from(lvl).is_finished());
+
+        let lvl = Level::parse(0, "#####\n#@ *#\n#####").unwrap();
+        assert_eq!(lvl.empty_goals, 0);
+        assert!(crate::CurrentLevel::from(lvl).is_finished());
+    }
+
+    #[test]
+    fn crate_ids_in_reading_order() {
+        let lvl = Level::parse(
+            0,
+            "#######\n\
+             #@ $ .#\n\
+             #$* . #\n\
+             # . $ #\n\
+             #######",
+        )
+        .unwrap();
+        let mut crates: Vec<_> = lvl.crates.iter().map(|(&pos, &id)| (id, pos)).collect();
+        crates.sort_by_key(|&(id, _)| id);
+        let positions: Vec<_> = crates.into_iter().map(|(_, pos)| pos).collect();
+        assert_eq!(
+            positions,
+            vec![
+                Position::new(3, 1),
+                Position::new(1, 2),
+                Position::new(2, 2),
+                Position::new(4, 3),
+            ]
+        );
+    }
+
     #[test]
     fn test_two_workers() {
         let s = "############\n\
@@ -199,8 +612,109 @@ mod test {
     }
 
     #[test]
-    #[should_panic]
+    fn parse_ice() {
+        let s = "#######\n\
+                 #@$~~.#\n\
+                 #######";
+        let lvl = Level::parse(0, s).unwrap();
+        assert_eq!(*lvl.background(Position::new(3, 1)), Background::Ice);
+        assert!(lvl.is_interior(Position::new(4, 1)));
+        assert_eq!(lvl.to_string(), s);
+    }
+
+    const ORIGINAL_LEVEL_2: &str = r#"
+############
+#..  #     ###
+#..  # $  $  #
+#..  #$####  #
+#..    @ ##  #
+#..  # #  $ ##
+###### ##$ $ #
+  # $  $ $ $ #
+  #    #     #
+  ############
+"#;
+
+    #[test]
+    fn estimate_difficulty() {
+        let simple = Level::parse(0, "#####\n#@$.#\n#####").unwrap();
+        let harder = Level::parse(1, ORIGINAL_LEVEL_2).unwrap();
+        assert!(simple.estimated_difficulty() < harder.estimated_difficulty());
+
+        // Everything in the simple level is part of a corridor.
+        assert_eq!(simple.estimated_difficulty(), 10 + 3 * 3 + 2);
+    }
+
+    #[test]
+    fn rotate_four_times() {
+        let lvl = Level::parse(0, ORIGINAL_LEVEL_2).unwrap();
+        let mut rotated = lvl.clone();
+        for _ in 0..4 {
+            rotated = rotated.transform(Transform::Rotate90);
+        }
+        assert_eq!(rotated.to_string(), lvl.to_string());
+
+        let rotated = lvl.transform(Transform::Rotate90);
+        assert_eq!((rotated.columns, rotated.rows), (lvl.rows, lvl.columns));
+        assert!(Level::parse(0, &rotated.to_string()).is_ok());
+        assert_eq!(
+            rotated.transform(Transform::Rotate270).to_string(),
+            lvl.to_string()
+        );
+    }
+
+    #[test]
+    fn transform_small_level() {
+        let lvl = Level::parse(0, "#####\n#@$.#\n#####").unwrap();
+        let flipped = lvl.transform(Transform::FlipHorizontal);
+        assert_eq!(flipped.to_string(), "#####\n#.$@#\n#####");
+        assert_eq!(
+            lvl.transform(Transform::Rotate90).to_string(),
+            "###\n#@#\n#$#\n#.#\n###"
+        );
+        assert_eq!(
+            lvl.transform(Transform::Rotate180).to_string(),
+            flipped.transform(Transform::FlipVertical).to_string()
+        );
+    }
+
+    #[test]
+    fn crop_empty_border() {
+        let s = "           \n\
+                 \x20          \n\
+                 \x20          \n\
+                 \x20  #####   \n\
+                 \x20  #@$.#   \n\
+                 \x20  #####   \n\
+                 \x20          \n\
+                 \x20          \n\
+                 \x20          ";
+        let lvl = Level::parse(0, s).unwrap();
+        assert_eq!((lvl.columns, lvl.rows), (11, 9));
+        assert_eq!(lvl.worker_position, Position::new(4, 4));
+
+        let cropped = lvl.cropped();
+        assert_eq!((cropped.columns, cropped.rows), (5, 3));
+        assert_eq!(cropped.worker_position, Position::new(1, 1));
+        assert_eq!(cropped.crates[&Position::new(2, 1)], 0);
+        assert_eq!(cropped.to_string(), "#####\n#@$.#\n#####");
+        assert_eq!(
+            Level::parse(0, &cropped.to_string()).unwrap().to_string(),
+            cropped.to_string()
+        );
+    }
+
+    #[test]
     fn invalid_char() {
-        let _ = Level::parse(0, "#######\n#.$@a #\n#######\n");
+        let res = Level::parse(0, "#######\n#.$@a #\n#######\n");
+        match res.unwrap_err() {
+            SokobanError::InvalidChar {
+                rank,
+                line,
+                column,
+                ch,
+            } => assert_eq!((rank, line, column, ch), (1, 2, 5, 'a')),
+            e => panic!("Unexpected error: {}", e),
+        }
     }
 }
@@ -1,6 +1,6 @@
 use std::collections::{HashMap, VecDeque};
 
-use crate::level::{Background, Level};
+use crate::level::{Background, Level, ParseOptions};
 use crate::position::*;
 use crate::util::*;
 
@@ -10,62 +10,106 @@ pub enum Foreground {
     None,
     Worker,
     Crate,
+
+    /// Any worker but the first one in levels with several workers.
+    OtherWorker,
 }
 
 fn char_to_cell(chr: char) -> Option<(Background, Foreground)> {
     match chr {
         '#' => Some((Background::Wall, Foreground::None)),
         ' ' => Some((Background::Empty, Foreground::None)),
+        '-' | '_' => Some((Background::Floor, Foreground::None)),
         '$' => Some((Background::Floor, Foreground::Crate)),
         '@' => Some((Background::Floor, Foreground::Worker)),
         '.' => Some((Background::Goal, Foreground::None)),
         '*' => Some((Background::Goal, Foreground::Crate)),
         '+' => Some((Background::Goal, Foreground::Worker)),
+        '&' => Some((Background::Floor, Foreground::OtherWorker)),
+        '%' => Some((Background::Goal, Foreground::OtherWorker)),
+        '~' => Some((Background::Ice, Foreground::None)),
         _ => None,
     }
 }
 
 pub(crate) struct LevelBuilder {
+    rank: usize,
     columns: usize,
     rows: usize,
     background: Vec<Background>,
     crates: HashMap<Position, usize>,
     worker_position: Position,
+    other_workers: Vec<Position>,
+    empty_goals: usize,
+
+    /// Whether to guess which empty cells are actually floor.
+    fill_floor: bool,
 }
 
+/// The largest number of cells a level may have. This protects against allocating huge amounts
+/// of memory for malformed files.
+pub(crate) const MAX_CELLS: usize = 1_000_000;
+
 fn is_empty_or_comment(s: &str) -> bool {
     s.is_empty() || s.trim().starts_with(';')
 }
 
 impl LevelBuilder {
+    /// Start building a level. Spaces inside the walls are taken to be floor.
     pub fn new(rank: usize, level_string: &str) -> Result<Self, SokobanError> {
+        Self::new_helper(rank, level_string, true)
+    }
+
+    /// Start building a level, treating spaces as empty no matter where they are. Floor has to
+    /// be marked explicitly using `-` or `_`, or be implied by a crate, goal or worker.
+    pub fn new_strict(rank: usize, level_string: &str) -> Result<Self, SokobanError> {
+        Self::new_helper(rank, level_string, false)
+    }
+
+    fn new_helper(rank: usize, level_string: &str, fill_floor: bool) -> Result<Self, SokobanError> {
         let lines: Vec<_> = level_string
             .lines()
+            .map(|x| x.trim_end_matches('\r'))
             .filter(|x| !is_empty_or_comment(x))
             .collect();
         let rows = lines.len();
         if rows == 0 {
             return Err(SokobanError::NoLevel(rank));
         }
+        // It is not clear how wide a tab is supposed to be, so reject them.
+        if let Some(y) = lines.iter().position(|x| x.contains('\t')) {
+            return Err(SokobanError::TabCharacter(rank, y + 1));
+        }
         let columns = lines.iter().map(|x| x.len()).max().unwrap();
         if columns == 0 {
             return Err(SokobanError::NoLevel(rank));
         }
+        if columns.saturating_mul(rows) > MAX_CELLS {
+            return Err(SokobanError::LevelTooLarge(
+                rank,
+                columns.saturating_mul(rows),
+            ));
+        }
 
         let mut found_worker = false;
         let mut worker_position = Position { x: 0, y: 0 };
+        let mut other_workers = vec![];
         let mut background = vec![Background::Empty; columns * rows];
         let mut crates = Vec::with_capacity(20);
 
         let mut goals_minus_crates = 0_i32;
+        let mut empty_goals = 0;
 
         let mut found_level_description = false;
         for (y, line) in lines.iter().enumerate() {
             let mut inside = false;
             for (x, chr) in line.chars().enumerate() {
-                let (bg, fg) = char_to_cell(chr).unwrap_or_else(|| {
-                    panic!("Invalid character '{}' in line {}, column {}.", chr, y, x)
-                });
+                let (bg, fg) = char_to_cell(chr).ok_or(SokobanError::InvalidChar {
+                    rank,
+                    line: y + 1,
+                    column: x + 1,
+                    ch: chr,
+                })?;
                 let index = y * columns + x;
                 background[index] = bg;
                 found_level_description = true;
@@ -76,6 +120,7 @@ impl LevelBuilder {
                     goals_minus_crates += 1;
                 } else if bg != Background::Goal && fg == Foreground::Crate {
                     goals_minus_crates -= 1;
+                    empty_goals += 1;
                 }
                 if fg == Foreground::Crate {
                     crates.push(Position::new(x, y));
@@ -86,7 +131,8 @@ impl LevelBuilder {
                     inside = true;
                 }
 
-                if inside
+                if fill_floor
+                    && inside
                     && bg == Background::Empty
                     && index >= columns
                     && background[index - columns] != Background::Empty
@@ -101,6 +147,8 @@ impl LevelBuilder {
                     }
                     worker_position = Position::new(x, y);
                     found_worker = true;
+                } else if fg == Foreground::OtherWorker {
+                    other_workers.push(Position::new(x, y));
                 }
             }
         }
@@ -112,80 +160,113 @@ impl LevelBuilder {
             return Err(SokobanError::CratesGoalsMismatch(rank, goals_minus_crates));
         }
 
+        // Crates are numbered in reading order, i.e. row by row, from left to right. Other code,
+        // e.g. anything converting levels to images, may rely on this.
         let swap = |(a, b)| (b, a);
         let crates = crates.into_iter().enumerate().map(swap).collect();
         Ok(Self {
+            rank,
             columns,
             rows,
             background,
             crates,
             worker_position,
+            other_workers,
+            empty_goals,
+            fill_floor,
         })
     }
 
-    pub fn build(mut self) -> Level {
-        self.correct_outside_cells();
-        Level {
+    /// Create the level. If `options.strict` is set, make sure it is enclosed by walls first. If
+    /// `options.require_goals` is set, make sure it contains at least one goal.
+    pub fn build(mut self, options: ParseOptions) -> Result<Level, SokobanError> {
+        if options.strict && !self.is_enclosed() {
+            return Err(SokobanError::NotEnclosed(self.rank));
+        }
+        if options.require_goals && !self.background.contains(&Background::Goal) {
+            return Err(SokobanError::NoGoals(self.rank));
+        }
+        if self.fill_floor {
+            self.correct_outside_cells();
+        }
+        Ok(Level {
             columns: self.columns,
             rows: self.rows,
             background: self.background,
             crates: self.crates,
             worker_position: self.worker_position,
-        }
+            other_workers: self.other_workers,
+            empty_goals: self.empty_goals,
+            title: None,
+        })
     }
 
-    /// Fix the mistakes of the heuristic used in `new()` for detecting which cells are on the
-    /// inside.
-    fn correct_outside_cells(&mut self) {
+    /// Check that the worker, the crates and the goals are surrounded by walls, i.e. that none
+    /// of the cells reachable from them without crossing a wall lies on the edge of the grid.
+    pub fn is_enclosed(&self) -> bool {
         let columns = self.columns;
+        let on_boundary = |pos: Position| {
+            pos.x == 0
+                || pos.y == 0
+                || pos.x as usize == columns - 1
+                || pos.y as usize == self.rows - 1
+        };
 
-        let mut queue = VecDeque::new();
         let mut visited = vec![false; self.background.len()];
-        visited[self.worker_position.to_index(columns)] = true;
-
-        let mut inside = visited.clone();
-
-        queue.push_back(self.worker_position);
-
-        for crate_pos in self.crates.keys() {
-            visited[crate_pos.to_index(columns)] = true;
-            queue.push_back(*crate_pos);
-        }
-
-        for (i, &bg) in self.background.iter().enumerate() {
-            match bg {
-                Background::Wall => visited[i] = true,
-                Background::Goal if !visited[i] => {
-                    inside[i] = true;
-                    visited[i] = true;
-                    queue.push_back(Position::from_index(i, columns));
-                }
-                _ => (),
-            }
+        let mut queue: VecDeque<_> = self
+            .background
+            .iter()
+            .enumerate()
+            .filter(|&(_, &bg)| bg == Background::Goal)
+            .map(|(i, _)| Position::from_index(i, columns))
+            .chain(self.crates.keys().cloned())
+            .chain(self.other_workers.iter().cloned())
+            .chain(Some(self.worker_position))
+            .collect();
+        for pos in &queue {
+            visited[pos.to_index(columns)] = true;
         }
 
-        // Flood fill from all positions added above
         while let Some(pos) = queue.pop_front() {
             use crate::Direction::*;
-            let i = pos.to_index(columns);
-            if let Background::Wall = self.background[i] {
-                continue;
-            } else {
-                inside[i] = true;
+            if on_boundary(pos) {
+                return false;
             }
             for n in [Up, Down, Left, Right].iter().map(|&x| pos.neighbour(x)) {
-                // The outermost rows and columns may only contain empty space and walls, so
-                // n has to bee within bounds.
                 let j = n.to_index(columns);
-                if !visited[j] {
+                if !visited[j] && !self.background[j].is_wall() {
                     visited[j] = true;
                     queue.push_back(n);
                 }
             }
         }
+        true
+    }
+
+    /// Fix the mistakes of the heuristic used in `new()` for detecting which cells are on the
+    /// inside.
+    fn correct_outside_cells(&mut self) {
+        let columns = self.columns;
+
+        // Everything connected to the worker, a crate or a goal without crossing a wall is inside
+        // the level.
+        let mut start = vec![self.worker_position];
+        start.extend(self.crates.keys().chain(&self.other_workers));
+        start.extend(
+            self.background
+                .iter()
+                .enumerate()
+                .filter(|&(_, &bg)| bg == Background::Goal)
+                .map(|(i, _)| Position::from_index(i, columns)),
+        );
+
+        let background = &self.background;
+        let inside = flood_fill(&start, columns, self.rows, |pos| {
+            background[pos.to_index(columns)] != Background::Wall
+        });
 
         for (i, bg) in self.background.iter_mut().enumerate() {
-            if !inside[i] && *bg == Background::Floor {
+            if !inside.contains(&Position::from_index(i, columns)) && *bg == Background::Floor {
                 *bg = Background::Empty;
             }
         }
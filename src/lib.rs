@@ -75,7 +75,7 @@ fn file_stem(p: &PathBuf) -> &str {
 pub fn convert_savegames() {
     use std::ffi::OsStr;
 
-    for entry in fs::read_dir(DATA_DIR.as_path()).unwrap() {
+    for entry in fs::read_dir(data_dir()).unwrap() {
         let entry = entry.unwrap();
         let path = entry.path();
         if path.is_file() && path.extension() == Some(OsStr::new("json")) {
@@ -86,47 +86,110 @@ pub fn convert_savegames() {
     }
 }
 
-struct CollectionStats {
+/// Summary of a level collection and the player's progress in it.
+#[derive(Debug, Clone)]
+pub struct CollectionStats {
+    /// The name of the file containing the collection.
     pub short_name: String,
+
+    /// The full name of the collection.
     pub name: String,
+
     pub total_levels: usize,
     pub solved_levels: usize,
+
+    /// The number of cells of the largest level in the collection.
+    pub max_level_cells: usize,
+
+    /// The rank of the largest level in the collection, or 0 if there are no levels.
+    pub max_level_rank: usize,
 }
 
 impl CollectionStats {
-    fn solved(&self) -> bool {
+    pub fn solved(&self) -> bool {
         self.total_levels == self.solved_levels
     }
-    fn started(&self) -> bool {
+    pub fn started(&self) -> bool {
         self.solved_levels > 0
     }
+    pub fn unsolved(&self) -> usize {
+        // The save file may list more solved levels than the collection has, e.g. after levels
+        // have been removed from it.
+        self.total_levels.saturating_sub(self.solved_levels)
+    }
 }
 
-fn gather_stats() -> Vec<CollectionStats> {
+fn collection_stats(short_name: &str) -> Result<CollectionStats, SokobanError> {
+    let collection = Collection::parse(short_name)?;
+    let state = CollectionState::load(collection.short_name());
+    // Pick the first of several equally large levels.
+    let (max_level_rank, max_level_cells) = collection
+        .levels()
+        .iter()
+        .enumerate()
+        .map(|(i, lvl)| (i + 1, lvl.columns * lvl.rows))
+        .max_by_key(|&(rank, cells)| (cells, std::cmp::Reverse(rank)))
+        .unwrap_or((0, 0));
+
+    Ok(CollectionStats {
+        short_name: short_name.to_string(),
+        name: collection.name().to_string(),
+        total_levels: collection.number_of_levels(),
+        solved_levels: state.number_of_solved_levels(),
+        max_level_cells,
+        max_level_rank,
+    })
+}
+
+/// The short names of all available collections in natural sort order.
+pub fn collection_names() -> Result<Vec<String>, SokobanError> {
+    use std::ffi::OsStr;
+
     // Find all level set files
-    let mut paths: Vec<PathBuf> = fs::read_dir(ASSETS.join("levels"))
-        .unwrap()
-        .map(|x| x.unwrap().path())
+    let mut paths: Vec<PathBuf> = fs::read_dir(levels_dir()?)?
+        .filter_map(|x| x.ok())
+        .map(|x| x.path())
+        .filter(|path| {
+            path.extension() == Some(OsStr::new("lvl"))
+                || path.extension() == Some(OsStr::new("slc"))
+        })
         .collect();
     paths.sort_by(|x, y| ::natord::compare(file_stem(x), file_stem(y)));
 
+    Ok(paths
+        .iter()
+        .map(|path| file_stem(path).to_string())
+        .collect())
+}
+
+/// Split a command line argument of the form `collection` or `collection:rank` into the name of
+/// the collection and the rank of the level to start at, if any. If the part after the last
+/// colon is not a number, the whole argument is taken to be the collection's name.
+pub fn parse_collection_arg(arg: &str) -> (String, Option<usize>) {
+    if let Some((name, rank)) = arg.rsplit_once(':') {
+        if let Ok(rank) = rank.parse() {
+            return (name.to_string(), Some(rank));
+        }
+    }
+    (arg.to_string(), None)
+}
+
+/// Load all available collections and summarise them, sorted by file name. Collections that
+/// cannot be parsed are skipped.
+pub fn list_collections() -> Vec<CollectionStats> {
     let mut result = vec![];
 
-    for path in paths {
-        if let Some(ext) = path.extension() {
-            use std::ffi::OsStr;
-            if ext == OsStr::new("lvl") || ext == OsStr::new("slc") {
-                let name = path.file_stem().and_then(|x| x.to_str()).unwrap();
-                let collection = Collection::parse_metadata(name).unwrap();
-                let state = CollectionState::load(collection.short_name());
-
-                result.push(CollectionStats {
-                    short_name: name.to_string(),
-                    name: collection.name().to_string(),
-                    total_levels: collection.number_of_levels(),
-                    solved_levels: state.number_of_solved_levels(),
-                });
-            }
+    let names = match collection_names() {
+        Ok(names) => names,
+        Err(e) => {
+            error!("{}", e);
+            return result;
+        }
+    };
+    for name in names {
+        match collection_stats(&name) {
+            Ok(stats) => result.push(stats),
+            Err(e) => warn!("Skipping collection {}: {}", name, e),
         }
     }
 
@@ -134,7 +197,7 @@ fn gather_stats() -> Vec<CollectionStats> {
 }
 
 pub fn print_collections_table() {
-    let stats = gather_stats();
+    let stats = list_collections();
 
     println!(
         " {}               {}",
@@ -172,7 +235,7 @@ pub fn print_collections_table() {
 }
 
 pub fn print_stats() {
-    let stats = gather_stats();
+    let stats = list_collections();
 
     let num_collections = stats.len();
     let num_levels: usize = stats.iter().map(|x| x.total_levels).sum();
@@ -193,4 +256,96 @@ pub fn print_stats() {
         finished_collections, finished_levels
     );
     println!("Started  {:>11}", collections_started);
+
+    if let Some(largest) = stats.iter().max_by_key(|x| x.max_level_cells) {
+        println!();
+        println!(
+            "Largest level: #{} of {} ({} cells)",
+            largest.max_level_rank, largest.name, largest.max_level_cells
+        );
+    }
+
+    let started: Vec<_> = stats
+        .iter()
+        .filter(|x| x.started() && !x.solved())
+        .collect();
+    if !started.is_empty() {
+        println!();
+        println!(
+            "{}",
+            Yellow
+                .bold()
+                .paint("Unsolved levels in started collections")
+        );
+        for collection in started {
+            println!(" {:<36}{:>5}", collection.name, collection.unsolved());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn stats_of_test_collection() {
+        let stats = collection_stats("test_2").unwrap();
+        assert_eq!(stats.name, "Simple collection for testing");
+        assert_eq!(stats.total_levels, 1);
+        assert_eq!(stats.max_level_cells, 8 * 12);
+        assert_eq!(stats.max_level_rank, 1);
+    }
+
+    #[test]
+    fn more_solved_than_total_levels() {
+        let stats = CollectionStats {
+            short_name: "shrunk".into(),
+            name: "Shrunk".into(),
+            total_levels: 2,
+            solved_levels: 3,
+            max_level_cells: 0,
+            max_level_rank: 0,
+        };
+        assert_eq!(stats.unsolved(), 0);
+    }
+
+    #[test]
+    fn collection_names_are_sorted() {
+        let names = collection_names().unwrap();
+        let microban_2 = names.iter().position(|x| x == "microban_2").unwrap();
+        assert_eq!(names[microban_2 + 1], "microban_3");
+        let sasquatch_9 = names.iter().position(|x| x == "sasquatch_9").unwrap();
+        assert_eq!(names[sasquatch_9 + 1], "sasquatch_10");
+        assert!(!names.iter().any(|x| x == "AUTHORS"));
+    }
+
+    #[test]
+    fn collection_arg() {
+        assert_eq!(
+            parse_collection_arg("original"),
+            ("original".to_string(), None)
+        );
+        assert_eq!(
+            parse_collection_arg("original:12"),
+            ("original".to_string(), Some(12))
+        );
+        assert_eq!(
+            parse_collection_arg("original:x"),
+            ("original:x".to_string(), None)
+        );
+    }
+
+    #[test]
+    fn list_original_collection() {
+        let collections = list_collections();
+        let original = collections
+            .iter()
+            .find(|x| x.short_name == "original")
+            .unwrap();
+        assert_eq!(original.total_levels, 50);
+
+        let collection = Collection::parse("original").unwrap();
+        let largest = &collection.levels()[original.max_level_rank - 1];
+        assert_eq!(largest.columns * largest.rows, original.max_level_cells);
+    }
 }
@@ -1,4 +1,4 @@
-use crate::command::Command;
+use crate::command::{Command, LevelManagement};
 
 /// A collection of macros, one for each of the F? keys, together with methods for recording and
 /// accessing them.
@@ -13,6 +13,10 @@ pub struct Macros {
 
     /// The macros available to the user at the moment.
     slots: [Vec<Command>; 12],
+
+    /// Should resetting the level be recorded as part of a macro? Other commands managing levels
+    /// are never recorded.
+    pub allow_management: bool,
 }
 
 impl Macros {
@@ -28,10 +32,25 @@ impl Macros {
         self.target_slot = Some(slot);
     }
 
+    /// The slot the macro currently being recorded will be stored in, if any.
+    pub fn recording_slot(&self) -> Option<u8> {
+        self.target_slot
+    }
+
+    /// Can the given command be part of a macro?
+    fn is_recordable(&self, cmd: &Command) -> bool {
+        match cmd {
+            Command::LevelManagement(LevelManagement::ResetLevel) => self.allow_management,
+            Command::LevelManagement(_) => false,
+            _ => true,
+        }
+    }
+
     /// Append a command to the macro currently being recorded. Return true if and only if a
-    /// target slot has been selected, that is, if `start_recording` has been called before.
+    /// target slot has been selected, that is, if `start_recording` has been called before, and
+    /// the command may be recorded.
     pub fn push(&mut self, cmd: &Command) -> bool {
-        if self.target_slot.is_some() {
+        if self.target_slot.is_some() && self.is_recordable(cmd) {
             // TODO We currently unroll macros to prevent any recursive calls. Should we allow some?
             // TODO handle Undo/Redo?
             self.tmp.push(cmd.clone());
@@ -56,12 +75,17 @@ impl Macros {
         }
     }
 
-    /// Retrieve the macro stored at the given slot.
-    pub fn get(&self, slot: u8) -> &[Command] {
+    /// Retrieve the macro stored at the given slot. Resetting the level is left out unless
+    /// `allow_management` is set.
+    pub fn get(&self, slot: u8) -> Vec<Command> {
         if self.target_slot == Some(slot) {
-            &[]
+            vec![]
         } else {
-            self.slots[slot as usize].as_ref()
+            self.slots[slot as usize]
+                .iter()
+                .filter(|cmd| self.is_recordable(cmd))
+                .cloned()
+                .collect()
         }
     }
 
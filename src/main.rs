@@ -34,6 +34,7 @@ use backend::{Command, LevelManagement};
 use glium::glutin::{
     self, dpi,
     event::{Event, KeyboardInput, VirtualKeyCode, WindowEvent},
+    event_loop::ControlFlow,
 };
 
 use sokoban_backend as backend;
@@ -43,8 +44,10 @@ use crate::gui::inputstate::*;
 
 use std::{collections::VecDeque, env, sync::mpsc::channel};
 
+use crate::backend::save::GameSnapshot;
 use crate::backend::{
-    convert_savegames, print_collections_table, print_stats, Collection, Game, TITLE,
+    convert_savegames, parse_collection_arg, print_collections_table, print_stats, Collection,
+    Game, TITLE,
 };
 
 fn main() {
@@ -58,7 +61,10 @@ fn main() {
         .arg(
             Arg::new("collection")
                 .value_name("collection")
-                .help("The level collection to load during startup")
+                .help(
+                    "The level collection to load during startup, optionally followed by a colon \
+                     and the number of the level to start at, e.g. original:12",
+                )
                 .index(1),
         )
         .arg(
@@ -75,6 +81,12 @@ fn main() {
                 .long("stats")
                 .action(ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("auto-advance")
+                .help("Go to the next level automatically after solving one")
+                .long("auto-advance")
+                .action(ArgAction::SetTrue),
+        )
         .arg(
             Arg::new("convert-savegames")
                 .help("Load and store all savegames to convert them to the latest file format")
@@ -94,19 +106,46 @@ fn main() {
         return;
     }
 
-    let collection_name = match matches.get_one::<&str>("collection") {
-        None | Some(&"") => {
-            env::var("SOKOBAN_COLLECTION").unwrap_or_else(|_| "original".to_string())
-        }
-        Some(c) => c.to_string(),
+    let (collection_arg, explicit_collection) = match matches.get_one::<&str>("collection") {
+        None | Some(&"") => (
+            env::var("SOKOBAN_COLLECTION").unwrap_or_else(|_| "original".to_string()),
+            false,
+        ),
+        Some(c) => (c.to_string(), true),
     };
+    let (collection_name, rank) = parse_collection_arg(&collection_arg);
 
     // With WINIT_HIDPI_FACTOR > 1, the textures become blurred. As we do not have a good use for
     // the DPI factor, we may as well fix it at 1.
     env::set_var("WINIT_HIDPI_FACTOR", "1");
 
-    let collection = Collection::parse(&collection_name).expect("Failed to load level set");
-    let game = Game::new(collection);
+    // If the previous session crashed, continue where it left off unless the player asked for a
+    // different collection or level.
+    let snapshot = GameSnapshot::load().filter(|snapshot| {
+        rank.is_none() && (!explicit_collection || snapshot.collection == collection_name)
+    });
+    let restored = snapshot.and_then(|snapshot| {
+        info!(
+            "Restoring level #{} of {}",
+            snapshot.rank, snapshot.collection
+        );
+        Game::restore(&snapshot)
+            .map_err(|e| warn!("Failed to restore the previous session: {}", e))
+            .ok()
+    });
+    let mut game = if let Some(game) = restored {
+        game
+    } else {
+        let collection = Collection::parse(&collection_name).expect("Failed to load level set");
+        let mut game = Game::new(collection).expect("Failed to start game");
+        if let Some(rank) = rank {
+            if game.goto_level(rank).is_err() {
+                warn!("Collection {} has no level #{}", collection_name, rank);
+            }
+        }
+        game
+    };
+    game.set_auto_advance(matches.get_flag("auto-advance"));
     let event_loop = glutin::event_loop::EventLoop::new();
     let mut gui = Gui::new(game, &event_loop);
 
@@ -132,7 +171,14 @@ fn main() {
                             ..
                         },
                     ..
-                } => return,
+                } => {
+                    // The game is closed properly, so there is nothing to recover.
+                    if let Err(e) = GameSnapshot::remove() {
+                        warn!("Failed to remove snapshot: {}", e);
+                    }
+                    *control_flow = ControlFlow::Exit;
+                    return;
+                }
 
                 WindowEvent::KeyboardInput {
                     input: KeyboardInput { state: Pressed, .. },
@@ -152,7 +198,11 @@ fn main() {
                             ..
                         },
                     ..
-                } => cmd = input_state.press_to_command(key, modifiers),
+                } => {
+                    cmd = input_state.press_to_command(key, modifiers, &gui.game.crate_positions());
+                    gui.set_show_reachable(input_state.show_reachable);
+                    gui.set_selected_crate(input_state.selected_crate);
+                }
 
                 WindowEvent::CursorMoved {
                     position: dpi::PhysicalPosition { x, y },
@@ -171,6 +221,9 @@ fn main() {
                     gui.need_to_redraw = true;
                 }
 
+                WindowEvent::Focused(false) => gui.game.pause_timer(),
+                WindowEvent::Focused(true) => gui.game.resume_timer(),
+
                 //WindowEvent::Refresh => gui.need_to_redraw = true,
                 _ => (),
             }
@@ -2,15 +2,55 @@ use std::convert::TryFrom;
 use std::fmt;
 
 use crate::direction::Direction;
+use crate::position::Position;
+
+/// Something that was moved from one cell to another as part of a `Move`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Displacement {
+    Worker {
+        id: usize,
+        from: Position,
+        to: Position,
+    },
+    Crate {
+        from: Position,
+        to: Position,
+    },
+}
 
 /// This structure contains everything needed to do or undo a Sokoban move.
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Move {
     /// Was a crate moved?
     pub moves_crate: bool,
 
     /// Where was the move directed?
     pub direction: Direction,
+
+    /// Was the crate behind the worker pulled along?
+    #[serde(default)]
+    pub pulls_crate: bool,
+
+    /// The index of the worker that moved. Most levels only have one worker, whose index is 0.
+    #[serde(default)]
+    pub worker: usize,
+
+    /// Everything that was moved when the move was performed, in the order it happened. Undoing
+    /// the move reverses these instead of working out again what the move did. Empty for moves
+    /// that have not been performed yet.
+    #[serde(skip)]
+    pub displacements: Vec<Displacement>,
+}
+
+/// Two moves are equal if the same worker moved in the same direction and did the same to crates,
+/// no matter whether and where they have been performed.
+impl PartialEq for Move {
+    fn eq(&self, other: &Self) -> bool {
+        self.moves_crate == other.moves_crate
+            && self.direction == other.direction
+            && self.pulls_crate == other.pulls_crate
+            && self.worker == other.worker
+    }
 }
 
 impl Move {
@@ -18,12 +58,40 @@ impl Move {
         Move {
             moves_crate,
             direction,
+            pulls_crate: false,
+            worker: 0,
+            displacements: vec![],
+        }
+    }
+
+    /// A step in the given direction pulling the crate behind the worker along.
+    pub fn pull(direction: Direction) -> Self {
+        Move {
+            moves_crate: false,
+            direction,
+            pulls_crate: true,
+            worker: 0,
+            displacements: vec![],
         }
     }
 
+    /// The same move made by the worker with the given index.
+    pub fn by_worker(self, worker: usize) -> Self {
+        Move { worker, ..self }
+    }
+
     /// Describe a move using one character signifying its direction. The character is upper case
-    /// if and only if `self.moves_crate` is true.
+    /// if and only if `self.moves_crate` is true. As there is no established notation for pulls,
+    /// they are written as arrows.
     pub fn to_char(&self) -> char {
+        if self.pulls_crate {
+            return match self.direction {
+                Direction::Left => '←',
+                Direction::Right => '→',
+                Direction::Up => '↑',
+                Direction::Down => '↓',
+            };
+        }
         let mut c = match self.direction {
             Direction::Left => 'l',
             Direction::Right => 'r',
@@ -37,6 +105,27 @@ impl Move {
     }
 }
 
+/// Remove pairs of consecutive moves in opposite directions that do not move a crate. As the
+/// worker ends up where it started, this does not change the outcome of the moves.
+pub fn simplify_moves(moves: &[Move]) -> Vec<Move> {
+    let mut result: Vec<Move> = Vec::with_capacity(moves.len());
+    for mv in moves {
+        match result.last() {
+            Some(prev)
+                if !prev.moves_crate
+                    && !mv.moves_crate
+                    && !prev.pulls_crate
+                    && !mv.pulls_crate
+                    && prev.direction == mv.direction.reverse() =>
+            {
+                result.pop();
+            }
+            _ => result.push(mv.clone()),
+        }
+    }
+    result
+}
+
 /// Parse a string representation of moves.
 pub fn parse(s: &str) -> Result<Vec<Move>, char> {
     s.chars().map(Move::try_from).collect::<Result<Vec<_>, _>>()
@@ -54,6 +143,10 @@ impl TryFrom<char> for Move {
     fn try_from(c: char) -> Result<Move, char> {
         use crate::Direction::*;
         let dir = match c {
+            '←' => return Ok(Move::pull(Left)),
+            '→' => return Ok(Move::pull(Right)),
+            '↑' => return Ok(Move::pull(Up)),
+            '↓' => return Ok(Move::pull(Down)),
             'l' | 'L' => Left,
             'r' | 'R' => Right,
             'u' | 'U' => Up,
@@ -76,6 +169,8 @@ mod test {
             assert_eq!(Ok(mv.clone()), Move::try_from(mv.to_char()));
             let mv = Move::new(dir, false);
             assert_eq!(Ok(mv.clone()), Move::try_from(mv.to_char()));
+            let mv = Move::pull(dir);
+            assert_eq!(Ok(mv.clone()), Move::try_from(mv.to_char()));
         }
     }
 
@@ -86,6 +181,18 @@ mod test {
         }
     }
 
+    #[test]
+    fn simplify() {
+        let to_string = |moves: Vec<Move>| moves.iter().map(Move::to_char).collect::<String>();
+        let simplify_str = |s| to_string(simplify_moves(&parse(s).unwrap()));
+
+        assert_eq!(simplify_str("lr"), "");
+        assert_eq!(simplify_str("urldr"), "r");
+        assert_eq!(simplify_str("Lr"), "Lr");
+        assert_eq!(simplify_str("lR"), "lR");
+        assert_eq!(simplify_str("rLRl"), "rLRl");
+    }
+
     #[test]
     fn parse_str() {
         let s = "UldrdddDddlLrrRRuLulLLUUdrdlduUDLR";
@@ -81,3 +81,71 @@ impl fmt::Debug for Position {
         write!(f, "({},{})", self.x, self.y)
     }
 }
+
+/// The extent of a rectangular grid of cells such as a level, with the top left cell at (0,0).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Bounds {
+    pub columns: usize,
+    pub rows: usize,
+}
+
+impl Bounds {
+    pub fn new(columns: usize, rows: usize) -> Self {
+        Bounds { columns, rows }
+    }
+
+    /// Does the grid have a cell at the given position?
+    pub fn contains(&self, pos: Position) -> bool {
+        pos.x >= 0 && pos.y >= 0 && (pos.x as usize) < self.columns && (pos.y as usize) < self.rows
+    }
+
+    /// The index of the given position in a vector of cells stored in row-major order.
+    pub fn index(&self, pos: Position) -> usize {
+        pos.to_index(self.columns)
+    }
+
+    /// The position of the cell with the given index in row-major order.
+    pub fn position(&self, index: usize) -> Position {
+        Position::from_index(index, self.columns)
+    }
+
+    /// Iterate over all positions in the grid in row-major order.
+    pub fn iter(&self) -> impl Iterator<Item = Position> {
+        let columns = self.columns;
+        (0..self.columns * self.rows).map(move |i| Position::from_index(i, columns))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bounds_contains() {
+        let bounds = Bounds::new(5, 3);
+        assert!(bounds.contains(Position::new(0, 0)));
+        assert!(bounds.contains(Position::new(4, 0)));
+        assert!(bounds.contains(Position::new(0, 2)));
+        assert!(bounds.contains(Position::new(4, 2)));
+
+        assert!(!bounds.contains(Position::new(5, 0)));
+        assert!(!bounds.contains(Position::new(0, 3)));
+        assert!(!bounds.contains(Position { x: -1, y: 0 }));
+        assert!(!bounds.contains(Position { x: 0, y: -1 }));
+    }
+
+    #[test]
+    fn bounds_iter() {
+        let bounds = Bounds::new(5, 3);
+        let positions: Vec<_> = bounds.iter().collect();
+        assert_eq!(positions.len(), 15);
+        assert!(positions.iter().all(|&pos| bounds.contains(pos)));
+        assert_eq!(positions[0], Position::new(0, 0));
+        assert_eq!(positions[14], Position::new(4, 2));
+
+        for (i, &pos) in positions.iter().enumerate() {
+            assert_eq!(bounds.index(pos), i);
+            assert_eq!(bounds.position(i), pos);
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use crate::util::data_dir;
+
+use super::SaveError;
+
+/// Preferences the player chose for a particular collection. These are stored separately from
+/// the `CollectionState`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CollectionSettings {
+    /// The rank of the level the player was playing most recently, or 0 if unknown.
+    #[serde(default)]
+    pub last_rank: usize,
+
+    /// The name of the texture theme to use.
+    #[serde(default)]
+    pub theme: String,
+}
+
+impl CollectionSettings {
+    /// Load the settings for the collection with the given name. If there are none, return the
+    /// default settings.
+    pub fn load(name: &str) -> Self {
+        Self::load_from(&Self::path(name)).unwrap_or_default()
+    }
+
+    /// Save the settings for the collection with the given name to disc.
+    pub fn save(&self, name: &str) -> Result<(), SaveError> {
+        self.save_to(&Self::path(name))
+    }
+
+    fn path(name: &str) -> PathBuf {
+        data_dir().join(format!("{}.settings.cbor", name))
+    }
+
+    fn load_from(path: &Path) -> Option<Self> {
+        let file = File::open(path).ok()?;
+        serde_cbor::from_reader(file).ok()
+    }
+
+    fn save_to(&self, path: &Path) -> Result<(), SaveError> {
+        let mut file = File::create(path)?;
+        serde_cbor::to_writer(&mut file, self)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let settings = CollectionSettings {
+            last_rank: 7,
+            theme: "pastel".into(),
+        };
+        settings.save("round_trip").unwrap();
+
+        let loaded = CollectionSettings::load("round_trip");
+        assert_eq!(loaded, settings);
+        assert_eq!(loaded.theme, "pastel");
+    }
+
+    #[test]
+    fn missing_settings() {
+        assert_eq!(
+            CollectionSettings::load("missing"),
+            CollectionSettings::default()
+        );
+    }
+}
@@ -1,10 +1,13 @@
+use std::convert::TryFrom;
 use std::fs::File;
 use std::path::Path;
 
-use crate::util::DATA_DIR;
+use crate::collection::Collection;
+use crate::current_level::CurrentLevel;
+use crate::util::data_dir;
 
 use super::level_state::*;
-use super::{SaveError, UpdateResponse};
+use super::{SaveError, Solution, UpdateResponse};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CollectionState {
@@ -50,7 +53,7 @@ impl CollectionState {
     }
 
     fn load_helper(name: &str, stats_only: bool) -> Self {
-        let path = DATA_DIR.join(name);
+        let path = data_dir().join(name);
 
         Self::load_cbor(&path, stats_only)
             .or_else(|| Self::load_json(&path, stats_only))
@@ -100,7 +103,7 @@ impl CollectionState {
     }
 
     fn save_cbor(&self, name: &str) -> Result<(), SaveError> {
-        let mut path = DATA_DIR.join(name);
+        let mut path = data_dir().join(name);
         path.set_extension("cbor");
         File::create(path)
             .map_err(SaveError::from)
@@ -108,6 +111,72 @@ impl CollectionState {
             .map(|_| ())
     }
 
+    /// Serialize the state as JSON so it can be transferred to a different machine.
+    pub fn export_json(&self) -> String {
+        ::serde_json::to_string(self).expect("Could not serialize collection state")
+    }
+
+    /// Merge a state previously exported using `export_json` into this one. For each level, the
+    /// best solutions of both states are kept. Unfinished levels never replace existing ones.
+    pub fn import_json(&mut self, json: &str) -> Result<(), SaveError> {
+        let other: CollectionState = ::serde_json::from_str(json)?;
+
+        for (index, level_state) in other.levels.into_iter().enumerate() {
+            if index < self.levels.len() && !level_state.is_finished() {
+                continue;
+            }
+            self.update(index, level_state);
+        }
+
+        self.collection_solved |= other.collection_solved;
+        self.levels_solved = self.levels_finished() as u32;
+
+        Ok(())
+    }
+
+    /// Record solutions from a solution file, given as pairs of a level's rank and its solution
+    /// in the LURD format. Each solution is replayed on the corresponding level of `collection`
+    /// and skipped if it does not solve the level. As levels are stored in order, a solution is
+    /// also skipped if the level before it has not been recorded yet.
+    pub fn import_solutions(
+        &mut self,
+        solutions: &[(usize, String)],
+        collection: &Collection,
+    ) -> Vec<UpdateResponse> {
+        let mut responses = vec![];
+        for (rank, lurd) in solutions {
+            let index = match rank.checked_sub(1) {
+                Some(index) if index <= self.levels.len() => index,
+                _ => {
+                    warn!("Cannot import solution for level #{}", rank);
+                    continue;
+                }
+            };
+            let level = if let Some(Ok(level)) = collection.iter_levels().nth(index) {
+                level
+            } else {
+                warn!("Cannot load level #{} to verify its solution", rank);
+                continue;
+            };
+
+            let lurd: String = lurd.chars().filter(|c| !c.is_whitespace()).collect();
+            let mut current_level: CurrentLevel = level.into();
+            if current_level.execute_moves(lurd.len(), &lurd).is_err() {
+                warn!("Invalid solution for level #{}", rank);
+                continue;
+            }
+            match Solution::try_from(&current_level) {
+                Ok(solution) => {
+                    responses.push(self.update(index, LevelState::new_solved(solution)))
+                }
+                Err(()) => warn!("The solution for level #{} does not solve it", rank),
+            }
+        }
+
+        self.levels_solved = self.levels_finished() as u32;
+        responses
+    }
+
     /// If a better or more complete solution for the current level is available, replace the old
     /// one with it.
     pub fn update(&mut self, index: usize, level_state: LevelState) -> UpdateResponse {
@@ -176,3 +245,69 @@ impl CollectionState {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level::Level;
+
+    fn solve(moves: &str) -> LevelState {
+        let mut lvl: CurrentLevel = Level::parse(
+            0,
+            "#####\n\
+             #@  #\n\
+             # $.#\n\
+             #####",
+        )
+        .unwrap()
+        .into();
+        lvl.execute_moves(moves.len(), moves).unwrap();
+        LevelState::new_solved(Solution::try_from(&lvl).unwrap())
+    }
+
+    fn state(levels: Vec<LevelState>) -> CollectionState {
+        let mut state = CollectionState::new("test");
+        for (i, level_state) in levels.into_iter().enumerate() {
+            state.update(i, level_state);
+        }
+        state.levels_solved = state.levels_finished() as u32;
+        state
+    }
+
+    #[test]
+    fn import_keeps_best_solutions() {
+        let mut local = state(vec![solve("dR"), solve("rldR")]);
+        let remote = state(vec![solve("rldR"), solve("dR"), solve("rldR")]);
+
+        local.import_json(&remote.export_json()).unwrap();
+
+        let expected = state(vec![solve("dR"), solve("dR"), solve("rldR")]);
+        assert_eq!(local.export_json(), expected.export_json());
+    }
+
+    #[test]
+    fn import_invalid_json() {
+        let mut local = CollectionState::new("test");
+        assert!(local.import_json("{").is_err());
+    }
+
+    #[test]
+    fn import_verified_solutions() {
+        let level = Level::parse(0, "#####\n#@  #\n# $.#\n#####").unwrap();
+        let collection = Collection::from_levels("test", &[level.clone(), level]);
+        let mut state = CollectionState::new("test");
+
+        let solutions = vec![(1, "dR".to_string()), (2, "rD".to_string())];
+        let responses = state.import_solutions(&solutions, &collection);
+
+        assert_eq!(responses.len(), 1);
+        assert_eq!(state.levels.len(), 1);
+        assert_eq!(state.number_of_solved_levels(), 1);
+        match state.levels[0] {
+            LevelState::Finished {
+                ref least_moves, ..
+            } => assert_eq!(least_moves.steps(), "dR"),
+            _ => panic!("Level should have been solved"),
+        }
+    }
+}
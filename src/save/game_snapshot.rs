@@ -0,0 +1,60 @@
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::util::data_dir;
+
+use super::SaveError;
+
+/// Everything needed to continue playing where the player left off, e.g. after a crash.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GameSnapshot {
+    /// The short name of the collection being played.
+    pub collection: String,
+
+    /// The rank of the current level.
+    pub rank: usize,
+
+    /// How many of the stored moves have actually been performed.
+    pub number_of_moves: usize,
+
+    /// The complete move log of the current level, including moves that have been undone.
+    pub moves: String,
+}
+
+impl GameSnapshot {
+    /// Load the most recent snapshot, if any.
+    pub fn load() -> Option<Self> {
+        Self::load_from(&Self::path())
+    }
+
+    /// Write the snapshot to disc, replacing any previous one.
+    pub fn save(&self) -> Result<(), SaveError> {
+        self.save_to(&Self::path())
+    }
+
+    /// Delete the snapshot, e.g. because the game was closed properly.
+    pub fn remove() -> Result<(), SaveError> {
+        match fs::remove_file(Self::path()) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Collection states are stored as `<name>.cbor`, so use a different extension to make sure
+    /// there is no clash with a collection called `snapshot`.
+    fn path() -> PathBuf {
+        data_dir().join("game.snapshot")
+    }
+
+    fn load_from(path: &Path) -> Option<Self> {
+        let file = File::open(path).ok()?;
+        serde_cbor::from_reader(file).ok()
+    }
+
+    fn save_to(&self, path: &Path) -> Result<(), SaveError> {
+        let mut file = File::create(path)?;
+        serde_cbor::to_writer(&mut file, self)?;
+        Ok(())
+    }
+}
@@ -7,7 +7,11 @@ use crate::current_level::*;
 pub enum LevelState {
     /// The level has not been finished.
     Started {
+        /// How many of the stored moves have actually been performed. Everything after that
+        /// point has been undone and can be redone after the level is reopened.
         number_of_moves: usize,
+
+        /// The complete move log, including moves that have been undone.
         moves: String,
     },
 
@@ -48,11 +52,9 @@ impl LevelState {
 
 impl<'a> From<&'a CurrentLevel> for LevelState {
     fn from(lvl: &'a CurrentLevel) -> Self {
-        if lvl.is_finished() {
-            let soln = Solution::try_from(lvl).unwrap();
-            LevelState::new_solved(soln)
-        } else {
-            LevelState::new_unsolved(lvl)
+        match Solution::try_from(lvl) {
+            Ok(soln) => LevelState::new_solved(soln),
+            Err(()) => LevelState::new_unsolved(lvl),
         }
     }
 }
@@ -1,14 +1,20 @@
 //! On-disc structures for storing which levels have been solved and the best solutions so far.
 
+mod collection_settings;
 mod collection_state;
+mod game_snapshot;
 mod level_state;
 mod solution;
+mod solve_log;
 
 use std::io;
 
+pub use self::collection_settings::*;
 pub use self::collection_state::*;
+pub use self::game_snapshot::*;
 pub use self::level_state::*;
 pub use self::solution::*;
+pub use self::solve_log::*;
 
 #[derive(Debug, Clone, Copy)]
 pub enum UpdateResponse {
@@ -23,8 +29,10 @@ pub enum SaveError {
 
     #[error("Failed to create CBOR: {0}")]
     CBOREncodeError(String),
-}
 
+    #[error("Failed to read JSON: {0}")]
+    JSONDecodeError(String),
+}
 
 impl From<io::Error> for SaveError {
     fn from(e: io::Error) -> Self {
@@ -37,3 +45,9 @@ impl From<::serde_cbor::error::Error> for SaveError {
         self::SaveError::CBOREncodeError(e.to_string())
     }
 }
+
+impl From<::serde_json::error::Error> for SaveError {
+    fn from(e: ::serde_json::error::Error) -> Self {
+        self::SaveError::JSONDecodeError(e.to_string())
+    }
+}
@@ -9,9 +9,26 @@ pub struct Solution {
     number_of_moves: usize,
     number_of_pushes: usize,
     steps: String,
+
+    /// How many moves were undone while finding this solution.
+    #[serde(default)]
+    undos: usize,
 }
 
 impl Solution {
+    /// The moves making up the solution, one character per move.
+    pub fn steps(&self) -> &str {
+        &self.steps
+    }
+
+    pub fn number_of_moves(&self) -> usize {
+        self.number_of_moves
+    }
+
+    pub fn number_of_pushes(&self) -> usize {
+        self.number_of_pushes
+    }
+
     /// Return a copy of either `self` or `other` with the smallest number of *worker* movements.
     pub fn min_moves(&self, other: &Solution) -> Self {
         match self.number_of_moves.cmp(&other.number_of_moves) {
@@ -41,17 +58,80 @@ impl Solution {
     }
 }
 
+/// Only levels solved without pulling crates or teleporting the worker yield a solution, as
+/// anything else cannot be replayed by other Sokoban programs.
 impl<'a> TryFrom<&'a CurrentLevel> for Solution {
     type Error = ();
     fn try_from(lvl: &'a CurrentLevel) -> Result<Solution, ()> {
-        if lvl.is_finished() {
+        if lvl.is_finished() && !lvl.has_pulls() && !lvl.has_teleported() {
             Ok(Solution {
                 number_of_moves: lvl.number_of_moves(),
                 number_of_pushes: lvl.number_of_pushes(),
                 steps: lvl.moves_to_string(),
+                undos: lvl.number_of_undos(),
             })
         } else {
             Err(())
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::direction::Direction::*;
+    use crate::level::Level;
+    use crate::position::Position;
+
+    #[test]
+    fn count_undos() {
+        let mut lvl: CurrentLevel = Level::parse(0, "######\n#@ $.#\n######").unwrap().into();
+        assert!(lvl.try_move(Right).is_ok());
+        assert!(lvl.undo());
+        assert!(lvl.redo());
+        assert!(lvl.undo());
+        assert!(lvl.try_move(Right).is_ok());
+        assert!(lvl.try_move(Right).is_ok());
+        assert!(lvl.is_finished());
+
+        let soln = Solution::try_from(&lvl).unwrap();
+        assert_eq!(soln.undos, 2);
+        assert_eq!(soln.number_of_moves, 2);
+    }
+
+    #[test]
+    fn solution_with_limited_history() {
+        let mut lvl: CurrentLevel = Level::parse(0, "#######\n#@  $.#\n#######").unwrap().into();
+        lvl.set_max_history(Some(1));
+        for _ in 0..3 {
+            assert!(lvl.try_move(Right).is_ok());
+        }
+
+        let soln = Solution::try_from(&lvl).unwrap();
+        assert_eq!(soln.steps(), "rrR");
+        assert_eq!(soln.number_of_moves, 3);
+        assert_eq!(soln.number_of_pushes, 1);
+    }
+
+    #[test]
+    fn no_solution_with_pulls() {
+        let mut lvl: CurrentLevel = Level::parse(0, "#######\n#  .@$#\n#######").unwrap().into();
+        assert!(lvl.pull(Left).is_ok());
+        assert!(lvl.pull(Left).is_ok());
+        assert!(lvl.is_finished());
+        assert!(lvl.has_pulls());
+        assert!(Solution::try_from(&lvl).is_err());
+    }
+
+    #[test]
+    fn no_solution_after_teleport() {
+        let mut lvl: CurrentLevel = Level::parse(0, "########\n#@   $.#\n########")
+            .unwrap()
+            .into();
+        assert!(lvl.teleport_worker(Position::new(4, 1)));
+        assert!(lvl.try_move(Right).is_ok());
+        assert!(lvl.is_finished());
+        assert!(lvl.has_teleported());
+        assert!(Solution::try_from(&lvl).is_err());
+    }
+}
@@ -0,0 +1,134 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::util::data_dir;
+
+use super::SaveError;
+
+/// A date, counted in days since the Unix epoch.
+pub type Day = u64;
+
+/// The current date in UTC.
+pub fn today() -> Day {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    seconds / (24 * 60 * 60)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SolveLogEntry {
+    pub day: Day,
+    pub collection: String,
+    pub rank: usize,
+}
+
+/// A record of which levels have been solved for the first time on which day, used for keeping
+/// track of the player's progress over time.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SolveLog {
+    entries: Vec<SolveLogEntry>,
+}
+
+impl SolveLog {
+    /// Load the log from disc, or create an empty one if there is none yet.
+    pub fn load() -> Self {
+        Self::load_from(&Self::path())
+            .or_else(|| Self::load_from(&data_dir().join("solvelog.cbor")))
+            .unwrap_or_default()
+    }
+
+    /// Write the log to disc.
+    pub fn save(&self) -> Result<(), SaveError> {
+        self.save_to(&Self::path())
+    }
+
+    /// Collection states are stored as `<name>.cbor`, so use a different extension to make sure
+    /// there is no clash with a collection called `solvelog`. Logs written using the old name,
+    /// `solvelog.cbor`, are still read.
+    fn path() -> PathBuf {
+        data_dir().join("solves.log")
+    }
+
+    fn load_from(path: &Path) -> Option<Self> {
+        let file = File::open(path).ok()?;
+        serde_cbor::from_reader(file).ok()
+    }
+
+    fn save_to(&self, path: &Path) -> Result<(), SaveError> {
+        let mut file = File::create(path)?;
+        serde_cbor::to_writer(&mut file, self)?;
+        Ok(())
+    }
+
+    /// Note that the level with the given rank was solved on the given day.
+    pub fn record(&mut self, day: Day, collection: &str, rank: usize) {
+        self.entries.push(SolveLogEntry {
+            day,
+            collection: collection.to_string(),
+            rank,
+        });
+    }
+
+    pub fn entries(&self) -> &[SolveLogEntry] {
+        &self.entries
+    }
+
+    /// How many levels were solved on the given day?
+    pub fn solved_on(&self, day: Day) -> usize {
+        self.entries.iter().filter(|e| e.day == day).count()
+    }
+
+    /// The number of consecutive days up to and including today on which at least one level was
+    /// solved. As long as the player may still solve a level today, a streak ending yesterday is
+    /// counted, too.
+    pub fn current_streak(&self) -> usize {
+        self.streak_ending(today())
+    }
+
+    fn streak_ending(&self, day: Day) -> usize {
+        let mut day = if self.solved_on(day) == 0 {
+            match day.checked_sub(1) {
+                Some(yesterday) => yesterday,
+                None => return 0,
+            }
+        } else {
+            day
+        };
+
+        let mut streak = 0;
+        while self.solved_on(day) > 0 {
+            streak += 1;
+            day = match day.checked_sub(1) {
+                Some(d) => d,
+                None => break,
+            };
+        }
+        streak
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn streak() {
+        let day = today();
+        let mut log = SolveLog::default();
+        log.record(day - 1, "original", 1);
+        log.record(day, "original", 2);
+        log.record(day, "original", 3);
+
+        assert_eq!(log.solved_on(day), 2);
+        assert_eq!(log.solved_on(day - 1), 1);
+        assert_eq!(log.current_streak(), 2);
+
+        // There is a gap two days before
+        log.record(day - 3, "microban_1", 1);
+        assert_eq!(log.current_streak(), 2);
+        assert_eq!(log.streak_ending(day + 1), 2);
+        assert_eq!(log.streak_ending(day + 2), 0);
+    }
+}
@@ -3,6 +3,17 @@
 pub struct Undo<T> {
     pub actions: Vec<T>,
     pub actions_performed: usize,
+
+    /// If set, only this many of the most recent actions are kept. Older actions can no longer be
+    /// undone and are not counted anymore.
+    max_history: Option<usize>,
+
+    /// How many actions have been dropped because of `max_history`.
+    forgotten: usize,
+
+    /// The number of actions performed when the checkpoint was set, counting forgotten ones. It
+    /// is cleared once the history diverges before that point or the point is forgotten.
+    checkpoint: Option<usize>,
 }
 
 impl<T> Undo<T>
@@ -13,17 +24,46 @@ where
         Self {
             actions: vec![],
             actions_performed: 0,
+            max_history: None,
+            forgotten: 0,
+            checkpoint: None,
         }
     }
 
-    pub fn is_empty(&self) -> bool {
-        self.actions_performed == 0
+    /// Forget old actions once more than `max_history` actions have been recorded.
+    pub fn set_max_history(&mut self, max_history: Option<usize>) {
+        self.max_history = max_history;
+    }
+
+    /// Forget all actions, keeping the limit on the number of actions.
+    pub fn clear(&mut self) {
+        *self = Self {
+            max_history: self.max_history,
+            ..Self::new()
+        };
+    }
+
+    /// Have all actions ever recorded been kept?
+    pub fn is_complete(&self) -> bool {
+        self.forgotten == 0
     }
 
     pub fn number_of_actions(&self) -> usize {
         self.actions_performed
     }
 
+    /// Remember the current point in the history.
+    pub fn set_checkpoint(&mut self) {
+        self.checkpoint = Some(self.forgotten + self.actions_performed);
+    }
+
+    /// The value `actions_performed` has to take to return to the checkpoint, if the checkpoint
+    /// is still part of the history.
+    pub fn checkpoint(&self) -> Option<usize> {
+        self.checkpoint
+            .map(|checkpoint| checkpoint - self.forgotten)
+    }
+
     pub fn count_matches<F>(&self, mut f: F) -> usize
     where
         F: FnMut(&T) -> bool,
@@ -34,10 +74,6 @@ where
             .count()
     }
 
-    pub fn last(&self) -> &T {
-        &self.actions[self.actions_performed - 1]
-    }
-
     pub fn to_string<F>(&self, f: F) -> String
     where
         F: FnMut(&T) -> char,
@@ -49,21 +85,43 @@ where
             .collect()
     }
 
-    /// When an action is performed, record the action in a log so it can later be undone.
-    pub fn record(&mut self, action: T) {
+    /// When an action is performed, record the action in a log so it can later be undone. Return
+    /// the oldest actions if they had to be forgotten because of `max_history`.
+    pub fn record(&mut self, action: T) -> Vec<T> {
         assert!(self.actions_performed <= self.actions.len());
         if self.actions.len() <= self.actions_performed {
             self.actions.push(action);
         } else {
             if self.actions[self.actions_performed] != action {
                 self.actions.truncate(self.actions_performed + 1);
+
+                // Later states are not reachable anymore.
+                let diverged_at = self.forgotten + self.actions_performed;
+                if self.checkpoint > Some(diverged_at) {
+                    self.checkpoint = None;
+                }
             }
 
             self.actions[self.actions_performed] = action;
         }
 
         self.actions_performed += 1;
+
+        // Forget the oldest actions, but never the ones that can still be redone.
+        let mut forgotten = vec![];
+        if let Some(max_history) = self.max_history {
+            let excess = self.actions.len().saturating_sub(max_history);
+            let excess = excess.min(self.actions_performed);
+            forgotten.extend(self.actions.drain(..excess));
+            self.actions_performed -= excess;
+            self.forgotten += excess;
+            if self.checkpoint < Some(self.forgotten) {
+                self.checkpoint = None;
+            }
+        }
+
         assert!(self.actions_performed <= self.actions.len());
+        forgotten
     }
 
     /// Get the most recent action from the log.
@@ -105,6 +163,9 @@ mod tests {
             Undo {
                 actions,
                 actions_performed,
+                max_history: None,
+                forgotten: 0,
+                checkpoint: None,
             }
         }
     }
@@ -117,6 +178,70 @@ mod tests {
         assert_eq!(sut.redo(), None);
     }
 
+    #[test]
+    fn limited_history() {
+        let mut sut = Undo::new();
+        sut.set_max_history(Some(3));
+        for x in 0..3 {
+            assert!(sut.record(x).is_empty());
+        }
+        assert_eq!(sut.record(3), vec![0]);
+        assert_eq!(sut.record(4), vec![1]);
+
+        assert!(!sut.is_complete());
+        assert_eq!(sut.number_of_actions(), 3);
+        assert_eq!(sut.undo(), Some(&4));
+        assert_eq!(sut.undo(), Some(&3));
+        assert_eq!(sut.undo(), Some(&2));
+        assert_eq!(sut.undo(), None);
+        assert_eq!(sut.redo(), Some(&2));
+    }
+
+    #[test]
+    fn checkpoint_survives_forgetting_earlier_actions() {
+        let mut sut = Undo::new();
+        sut.set_max_history(Some(3));
+        sut.record(0);
+        sut.record(1);
+        sut.set_checkpoint();
+        assert_eq!(sut.checkpoint(), Some(2));
+
+        sut.record(2);
+        sut.record(3);
+        assert_eq!(sut.checkpoint(), Some(1));
+
+        // Once the actions leading up to the checkpoint are forgotten, it cannot be reached
+        sut.record(4);
+        sut.record(5);
+        assert_eq!(sut.checkpoint(), None);
+    }
+
+    #[test]
+    fn checkpoint_cleared_when_history_diverges() {
+        let mut sut = Undo::new();
+        for x in 0..3 {
+            sut.record(x);
+        }
+        sut.set_checkpoint();
+
+        // Redoing the same actions keeps the checkpoint
+        sut.undo();
+        sut.undo();
+        sut.record(1);
+        assert_eq!(sut.checkpoint(), Some(3));
+
+        sut.undo();
+        sut.record(7);
+        assert_eq!(sut.checkpoint(), None);
+
+        // Diverging after the checkpoint does not matter
+        sut.set_checkpoint();
+        sut.record(8);
+        sut.undo();
+        sut.record(9);
+        assert_eq!(sut.checkpoint(), Some(2));
+    }
+
     #[quickcheck]
     fn undo_should_return_most_recent_action(mut sut: Undo<u32>, x: u32) {
         let num_actions = sut.actions_performed;
@@ -1,20 +1,40 @@
+use std::collections::{HashSet, VecDeque};
+use std::env;
 use std::io;
 use std::path::PathBuf;
 
 use directories::{ProjectDirs};
 
+use crate::direction::DIRECTIONS;
+use crate::position::{Bounds, Position};
+
 pub const TITLE: &str = "Sokoban";
 
 lazy_static! {
+    /// Where to store save files. May be overridden by setting `SOKOBAN_DATA_DIR`.
     pub static ref DATA_DIR: PathBuf = {
+        if let Some(dir) = env::var_os("SOKOBAN_DATA_DIR") {
+            return dir.into();
+        }
         let proj_dirs = ProjectDirs::from("de", "yzhs", "sokoban").unwrap();
         proj_dirs.data_dir().into()
     };
 
-    /// Path to the assets directory
-    pub static ref ASSETS: PathBuf = ::find_folder::Search::ParentsThenKids(3, 3)
-            .for_folder("assets")
-            .unwrap();
+    /// Path to the assets directory. May be overridden by setting `SOKOBAN_ASSETS`. Otherwise,
+    /// look for it around the current working directory and then next to the executable. The
+    /// directory may not exist, in which case loading levels fails.
+    pub static ref ASSETS: PathBuf = {
+        if let Some(dir) = env::var_os("SOKOBAN_ASSETS") {
+            return dir.into();
+        }
+        if let Ok(dir) = ::find_folder::Search::ParentsThenKids(3, 3).for_folder("assets") {
+            return dir;
+        }
+        env::current_exe()
+            .ok()
+            .and_then(|exe| exe.parent().map(|dir| dir.join("assets")))
+            .unwrap_or_else(|| PathBuf::from("assets"))
+    };
 
 }
 
@@ -37,6 +57,64 @@ pub enum SokobanError {
 
     #[error("Empty description for level #{0}")]
     NoLevel(usize),
+
+    #[error("Level #{0} is not enclosed by walls")]
+    NotEnclosed(usize),
+
+    #[error("Level #{0} does not contain any goals")]
+    NoGoals(usize),
+
+    #[error("Level #{0} contains a tab in line {1}")]
+    TabCharacter(usize, usize),
+
+    #[error("Level #{rank} contains an invalid character '{ch}' in line {line}, column {column}")]
+    InvalidChar {
+        rank: usize,
+        line: usize,
+        column: usize,
+        ch: char,
+    },
+
+    #[error("Level #{0} is too large ({1} cells)")]
+    LevelTooLarge(usize, usize),
+
+    #[error("Levels have different sizes: {0}x{1} and {2}x{3}")]
+    SizeMismatch(usize, usize, usize, usize),
+
+    #[error("Collection {0} does not contain any levels")]
+    EmptyCollection(String),
+
+    #[error("There is no collection after {0}")]
+    NoNextCollection(String),
+
+    #[error("Cannot find any levels in {0}, try setting SOKOBAN_ASSETS")]
+    NoLevelsDirectory(String),
+
+    #[error("Collection {0} was loaded with different options than the others")]
+    ParseOptionsMismatch(String),
+}
+
+/// The directory save files are written to, i.e. `DATA_DIR`.
+#[cfg(not(test))]
+pub(crate) fn data_dir() -> PathBuf {
+    DATA_DIR.clone()
+}
+
+/// Each unit test gets its own empty data directory so tests can neither see each other's save
+/// files nor modify the player's.
+#[cfg(test)]
+pub(crate) fn data_dir() -> PathBuf {
+    tests::TEST_DATA_DIR.with(|dir| dir.0.clone())
+}
+
+/// The directory containing all level collections.
+pub fn levels_dir() -> Result<PathBuf, SokobanError> {
+    let dir = ASSETS.join("levels");
+    if dir.is_dir() {
+        Ok(dir)
+    } else {
+        Err(SokobanError::NoLevelsDirectory(dir.display().to_string()))
+    }
 }
 
 /// Automatically wrap io errors
@@ -52,3 +130,77 @@ impl From<quick_xml::Error> for SokobanError {
         SokobanError::XmlError(e.to_string())
     }
 }
+
+/// Find all cells of a `columns` by `rows` grid that can be reached from any of the positions in
+/// `start` by repeatedly stepping to a neighbouring cell that is `passable`. The starting positions
+/// themselves are always included.
+pub(crate) fn flood_fill(
+    start: &[Position],
+    columns: usize,
+    rows: usize,
+    passable: impl Fn(Position) -> bool,
+) -> HashSet<Position> {
+    let bounds = Bounds::new(columns, rows);
+    let mut visited: HashSet<_> = start.iter().copied().collect();
+    let mut queue: VecDeque<_> = start.iter().copied().collect();
+
+    while let Some(pos) = queue.pop_front() {
+        for neighbour in DIRECTIONS.iter().map(|&dir| pos.neighbour(dir)) {
+            if bounds.contains(neighbour) && passable(neighbour) && visited.insert(neighbour) {
+                queue.push_back(neighbour);
+            }
+        }
+    }
+
+    visited
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// A temporary directory that is removed once the test owning it is done.
+    pub(super) struct TestDataDir(pub PathBuf);
+
+    impl Drop for TestDataDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    static NEXT_TEST_DATA_DIR: AtomicUsize = AtomicUsize::new(0);
+
+    thread_local! {
+        pub(super) static TEST_DATA_DIR: TestDataDir = {
+            let n = NEXT_TEST_DATA_DIR.fetch_add(1, Ordering::SeqCst);
+            let dir = env::temp_dir().join(format!("sokoban_test_{}_{}", std::process::id(), n));
+            fs::create_dir_all(&dir).unwrap();
+            TestDataDir(dir)
+        };
+    }
+
+    #[test]
+    fn data_dir_per_test() {
+        let dir = data_dir();
+        assert!(dir.is_dir());
+        assert_ne!(dir, *DATA_DIR);
+        let other = std::thread::spawn(data_dir).join().unwrap();
+        assert_ne!(dir, other);
+        assert!(!other.exists());
+    }
+
+    #[test]
+    fn flood_fill_stops_at_wall() {
+        // A 4x3 grid with a wall in the third column
+        let passable = |pos: Position| pos.x != 2;
+        let cells = flood_fill(&[Position::new(0, 1)], 4, 3, passable);
+        assert_eq!(cells.len(), 6);
+        assert!(cells.iter().all(|pos| pos.x < 2));
+
+        let cells = flood_fill(&[Position::new(0, 0), Position::new(3, 2)], 4, 3, passable);
+        assert_eq!(cells.len(), 9);
+        assert!(!cells.contains(&Position::new(2, 1)));
+    }
+}
@@ -0,0 +1,26 @@
+use std::env;
+use std::fs;
+
+use sokoban_backend as backend;
+
+use crate::backend::{collection_names, Collection, ASSETS};
+
+#[test]
+fn assets_dir_from_env() {
+    let dir = env::temp_dir().join(format!("sokoban_assets_{}", std::process::id()));
+    fs::create_dir_all(dir.join("levels")).unwrap();
+    fs::write(dir.join("levels/tiny.lvl"), "Tiny\n\n#####\n#@$.#\n#####\n").unwrap();
+    env::set_var("SOKOBAN_ASSETS", &dir);
+
+    assert_eq!(*ASSETS, dir);
+    assert_eq!(collection_names().unwrap(), vec!["tiny"]);
+    let collection = Collection::parse("tiny").unwrap();
+    assert_eq!(collection.name(), "Tiny");
+    assert_eq!(collection.number_of_levels(), 1);
+    assert!(Collection::parse("original").is_err());
+
+    fs::remove_dir_all(dir.join("levels")).unwrap();
+    assert!(collection_names().is_err());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
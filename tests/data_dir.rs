@@ -0,0 +1,21 @@
+use std::env;
+use std::fs;
+
+use sokoban_backend as backend;
+
+use crate::backend::save::CollectionState;
+use crate::backend::DATA_DIR;
+
+#[test]
+fn data_dir_from_env() {
+    let dir = env::temp_dir().join(format!("sokoban_data_dir_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    env::set_var("SOKOBAN_DATA_DIR", &dir);
+
+    assert_eq!(*DATA_DIR, dir);
+    let mut state = CollectionState::new("test_collection");
+    state.save("test_collection").unwrap();
+    assert!(dir.join("test_collection.cbor").is_file());
+
+    fs::remove_dir_all(&dir).unwrap();
+}